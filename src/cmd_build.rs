@@ -58,6 +58,65 @@ pub struct BuildArgs {
     #[arg(long = "annotation", value_name = "KEY=VALUE")]
     annotations: Vec<String>,
 
+    /// Add, override or remove an environment variable
+    ///
+    /// Format: KEY=VALUE to set, KEY- to remove, or - to clear all. Same merge
+    /// rules as --label, applied against the base config's env list.
+    #[arg(long = "env", value_name = "KEY=VALUE|KEY-|-")]
+    env: Vec<String>,
+
+    /// Override the image user
+    #[arg(long = "user", value_name = "USER")]
+    user: Option<String>,
+
+    /// Override the image working directory
+    #[arg(long = "working-dir", value_name = "PATH")]
+    working_dir: Option<String>,
+
+    /// Override the image stop signal
+    #[arg(long = "stop-signal", value_name = "SIGNAL")]
+    stop_signal: Option<String>,
+
+    /// Replace the entrypoint (repeat for multiple elements)
+    #[arg(long = "entrypoint", value_name = "ARG")]
+    entrypoint: Vec<String>,
+    /// Append an element to the entrypoint
+    #[arg(long = "entrypoint-add", value_name = "ARG")]
+    entrypoint_add: Vec<String>,
+    /// Clear the entrypoint
+    #[arg(long = "entrypoint-clear")]
+    entrypoint_clear: bool,
+
+    /// Replace the command (repeat for multiple elements)
+    #[arg(long = "cmd", value_name = "ARG")]
+    cmd: Vec<String>,
+    /// Append an element to the command
+    #[arg(long = "cmd-add", value_name = "ARG")]
+    cmd_add: Vec<String>,
+    /// Clear the command
+    #[arg(long = "cmd-clear")]
+    cmd_clear: bool,
+
+    /// Replace the exposed ports (repeat for multiple elements)
+    #[arg(long = "exposed-port", value_name = "PORT")]
+    exposed_port: Vec<String>,
+    /// Append an exposed port
+    #[arg(long = "exposed-port-add", value_name = "PORT")]
+    exposed_port_add: Vec<String>,
+    /// Clear the exposed ports
+    #[arg(long = "exposed-port-clear")]
+    exposed_port_clear: bool,
+
+    /// Replace the volumes (repeat for multiple elements)
+    #[arg(long = "volume", value_name = "PATH")]
+    volume: Vec<String>,
+    /// Append a volume
+    #[arg(long = "volume-add", value_name = "PATH")]
+    volume_add: Vec<String>,
+    /// Clear the volumes
+    #[arg(long = "volume-clear")]
+    volume_clear: bool,
+
     /// Unix timestamp used as the creation time for the OCI image and as
     /// the maximum mtime for files without a known build time.
     #[arg(
@@ -103,6 +162,79 @@ pub struct BuildArgs {
     /// absolute.
     #[arg(long = "prune", value_name = "PATH")]
     prune: Vec<Utf8PathBuf>,
+
+    /// Restrict chunking to paths matching this pattern
+    ///
+    /// Absolute glob/prefix pattern (supports `*` and `**`). When given, only
+    /// matching paths (and the directories leading to them) are scanned. Can be
+    /// specified multiple times.
+    #[arg(long = "include", value_name = "PATTERN")]
+    include: Vec<String>,
+
+    /// Exclude paths matching this pattern from chunking
+    ///
+    /// Absolute glob/prefix pattern (supports `*` and `**`). Applied after
+    /// includes. Can be specified multiple times.
+    #[arg(long = "exclude", value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Compute SELinux labels from a policy `file_contexts` file
+    ///
+    /// When set, chunkah synthesizes a `security.selinux` xattr for each file
+    /// by matching it against the given `file_contexts` database (libselinux
+    /// semantics), rather than relying on the container runtime. Useful for
+    /// building fully-labeled bootc/ostree layers.
+    #[arg(long = "selinux-policy", value_name = "PATH")]
+    selinux_policy: Option<Utf8PathBuf>,
+
+    /// Force dense output, disabling sparse-file detection
+    ///
+    /// By default, files with holes are emitted as GNU sparse tar entries.
+    /// This flag materializes every file in full instead.
+    #[arg(long)]
+    no_sparse: bool,
+
+    /// Reuse content digests from a persistent scan cache at this path
+    ///
+    /// Re-hashing every regular file dominates a rescan of a mostly-unchanged
+    /// rootfs. When an entry's size, inode, and mtime match a previous run's,
+    /// its digest is taken from the cache instead of being recomputed; the
+    /// cache is rewritten with the current observations afterwards.
+    #[arg(long = "scan-cache", value_name = "PATH")]
+    scan_cache: Option<Utf8PathBuf>,
+
+    /// How to encode xattr keys in SCHILY.xattr PAX records
+    ///
+    /// `schily-raw` writes keys verbatim (GNU tar); `libarchive-urlencoded`
+    /// percent-encodes non-printable/reserved bytes (libarchive).
+    #[arg(long = "xattr-encoding", value_enum, default_value_t)]
+    xattr_encoding: crate::scan::XattrEncoding,
+
+    /// Cache the computed component-to-path mapping at this path
+    ///
+    /// Querying the package database and canonicalizing every owned path is
+    /// expensive. When a cache fingerprinted over the installed package set
+    /// (and the package database file) matches, the mapping is deserialized
+    /// and reused instead of recomputed.
+    #[arg(long = "component-cache", value_name = "PATH")]
+    component_cache: Option<Utf8PathBuf>,
+
+    /// Verify packaged files against their recorded digests (`rpm -V` style)
+    ///
+    /// Regular files whose on-disk content diverges from the digest recorded
+    /// by the package are treated as locally modified and left unclaimed, so
+    /// they fall into chunkah/unclaimed rather than churning an
+    /// otherwise-stable component layer across images.
+    #[arg(long)]
+    verify: bool,
+
+    /// Exempt `%config` files from `--verify`
+    ///
+    /// Config files are routinely edited after install and are expected to
+    /// differ from the packaged content, so their divergence is not treated as
+    /// a reason to unclaim them.
+    #[arg(long, requires = "verify")]
+    verify_exempt_config: bool,
 }
 
 impl BuildArgs {
@@ -132,6 +264,61 @@ impl BuildArgs {
             volumes
         );
 
+        // Everything below applies CLI overrides on top of the copied base
+        // fields, so precedence is base-config -> CLI, matching labels.
+
+        // scalars: a provided value replaces the base
+        if let Some(user) = &self.user {
+            builder = builder.user(user.clone());
+        }
+        if let Some(working_dir) = &self.working_dir {
+            builder = builder.working_dir(working_dir.clone());
+        }
+        if let Some(stop_signal) = &self.stop_signal {
+            builder = builder.stop_signal(stop_signal.clone());
+        }
+
+        // env: same key/value merge rules as labels, applied to the env list
+        if !self.env.is_empty() {
+            let base = config.env().clone().unwrap_or_default();
+            let seeded =
+                parse_key_value_pairs(&base, HashMap::new()).context("parsing base env")?;
+            let merged = parse_key_value_pairs(&self.env, seeded).context("parsing env")?;
+            // sort for deterministic output
+            let mut env: Vec<String> = merged.into_iter().map(|(k, v)| format!("{k}={v}")).collect();
+            env.sort();
+            builder = builder.env(env);
+        }
+
+        // ordered list fields: clear -> replace -> append
+        if let Some(entrypoint) = apply_list_ops(
+            config.entrypoint(),
+            &self.entrypoint,
+            &self.entrypoint_add,
+            self.entrypoint_clear,
+        ) {
+            builder = builder.entrypoint(entrypoint);
+        }
+        if let Some(cmd) = apply_list_ops(config.cmd(), &self.cmd, &self.cmd_add, self.cmd_clear) {
+            builder = builder.cmd(cmd);
+        }
+        if let Some(ports) = apply_list_ops(
+            config.exposed_ports(),
+            &self.exposed_port,
+            &self.exposed_port_add,
+            self.exposed_port_clear,
+        ) {
+            builder = builder.exposed_ports(ports);
+        }
+        if let Some(volumes) = apply_list_ops(
+            config.volumes(),
+            &self.volume,
+            &self.volume_add,
+            self.volume_clear,
+        ) {
+            builder = builder.volumes(volumes);
+        }
+
         // labels; CLI args override config
         let labels =
             parse_key_value_pairs(&self.labels, config.labels().clone().unwrap_or_default())
@@ -144,6 +331,32 @@ impl BuildArgs {
     }
 }
 
+/// Apply ordered list operations (clear, full replacement, append) to an
+/// optional base list, returning `None` when no operation was requested so the
+/// copied base field is left untouched.
+fn apply_list_ops(
+    base: &Option<Vec<String>>,
+    replace: &[String],
+    add: &[String],
+    clear: bool,
+) -> Option<Vec<String>> {
+    if !clear && replace.is_empty() && add.is_empty() {
+        return None;
+    }
+
+    // clear drops the base; a non-empty replacement takes over from there.
+    let mut list = if clear {
+        Vec::new()
+    } else {
+        base.clone().unwrap_or_default()
+    };
+    if !replace.is_empty() {
+        list = replace.to_vec();
+    }
+    list.extend_from_slice(add);
+    Some(list)
+}
+
 pub fn run(args: &BuildArgs) -> Result<()> {
     tracing::info!(rootfs = %args.rootfs, "starting build");
 
@@ -186,15 +399,41 @@ pub fn run(args: &BuildArgs) -> Result<()> {
     let rootfs = Dir::open_ambient_dir(args.rootfs.as_std_path(), ambient_authority())
         .with_context(|| format!("opening rootfs {}", args.rootfs))?;
 
+    let matcher = crate::matcher::Matcher::new(args.include.clone(), args.exclude.clone());
+
+    let selinux_policy = args
+        .selinux_policy
+        .as_deref()
+        .map(crate::selinux::FileContexts::load)
+        .transpose()
+        .context("loading SELinux policy")?;
+
     let files = crate::scan::Scanner::new(&rootfs)
         .skip_special_files(args.skip_special_files)
         .prune(&args.prune)?
+        .matcher(matcher)
+        .selinux_policy(selinux_policy)
+        .no_sparse(args.no_sparse)
+        .cache(args.scan_cache.as_deref())
         .scan()
         .with_context(|| format!("scanning {} for files", args.rootfs))?;
     tracing::info!(files = files.len(), "scan complete");
 
-    let repos =
-        ComponentsRepos::load(&rootfs, &files, created_epoch).context("loading components")?;
+    let verify = args.verify.then_some(crate::components::VerifyOptions {
+        exempt_config: args.verify_exempt_config,
+    });
+    // The mtime ceiling for reproducible layers is the explicitly supplied
+    // SOURCE_DATE_EPOCH (if any), not the fallback current time baked into
+    // `created_epoch`.
+    let repos = ComponentsRepos::load(
+        &rootfs,
+        &files,
+        created_epoch,
+        args.component_cache.as_deref(),
+        verify,
+        args.source_date_epoch,
+    )
+    .context("loading components")?;
     if repos.is_empty() {
         anyhow::bail!("no supported component repo found in rootfs");
     }
@@ -216,6 +455,7 @@ pub fn run(args: &BuildArgs) -> Result<()> {
     let builder = Builder::new(&rootfs, components)
         .context("creating builder")?
         .compression(compression)
+        .xattr_encoding(args.xattr_encoding)
         .annotations(annotations)
         .config(image_config);
 
@@ -363,6 +603,7 @@ fn pack_components(
             PackItem {
                 size,
                 stability: comp.stability,
+                previous_group: None,
             }
         })
         .collect();
@@ -654,4 +895,52 @@ mod tests {
         assert_eq!(labels.get("override-me"), Some(&"new-value".to_string()));
         assert_eq!(labels.get("new-label"), Some(&"second".to_string()));
     }
+
+    #[test]
+    fn test_build_image_config_field_overrides() {
+        // Base config with env, entrypoint, cmd, and a scalar.
+        let json = r#"{
+            "Env": ["PATH=/usr/bin", "DEBUG=0"],
+            "Entrypoint": ["/usr/bin/app"],
+            "Cmd": ["--serve"],
+            "WorkingDir": "/old"
+        }"#;
+        let parsed = parse_config(json).unwrap();
+
+        let args = BuildArgs {
+            // merge: override DEBUG, drop nothing, add a key
+            env: vec!["DEBUG=1".into(), "EXTRA=yes".into()],
+            user: Some("app".into()),
+            working_dir: Some("/new".into()),
+            // append to the base entrypoint
+            entrypoint_add: vec!["--flag".into()],
+            // fully replace cmd
+            cmd: vec!["--other".into()],
+            // clear volumes
+            volume_clear: true,
+            ..Default::default()
+        };
+
+        let image_config = build_image_config(&args, parsed.config, 1, "amd64").unwrap();
+        let config = image_config.config().as_ref().unwrap();
+
+        // env is merged and sorted deterministically
+        assert_eq!(
+            config.env().as_ref().unwrap(),
+            &vec![
+                "DEBUG=1".to_string(),
+                "EXTRA=yes".to_string(),
+                "PATH=/usr/bin".to_string(),
+            ]
+        );
+        assert_eq!(config.user(), &Some("app".to_string()));
+        assert_eq!(config.working_dir(), &Some("/new".to_string()));
+        assert_eq!(
+            config.entrypoint(),
+            &Some(vec!["/usr/bin/app".to_string(), "--flag".to_string()])
+        );
+        assert_eq!(config.cmd(), &Some(vec!["--other".to_string()]));
+        // cleared volumes become an empty set rather than the base value
+        assert_eq!(config.volumes(), &Some(vec![]));
+    }
 }
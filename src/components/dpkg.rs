@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use cap_std_ext::cap_std::fs::Dir;
+use indexmap::IndexMap;
+
+use crate::utils::canonicalize_parent_path;
+
+use super::{ComponentId, ComponentInfo, ComponentsRepo, FileMap, FileType};
+
+const REPO_NAME: &str = "dpkg";
+
+/// The dpkg/apt database lives under these paths; like [`RPMDB_PATHS`] for RPM
+/// images, they are churny bookkeeping state that should fall into
+/// chunkah/unclaimed rather than into a stable component layer.
+///
+/// [`RPMDB_PATHS`]: super::rpm
+const DPKGDB_PATHS: &[&str] = &["var/lib/dpkg", "var/lib/apt"];
+
+/// Directory holding dpkg's per-package bookkeeping (`*.list`, `*.conffiles`).
+const DPKG_INFO_DIR: &str = "var/lib/dpkg/info";
+
+/// The status database enumerating every installed package.
+const DPKG_STATUS: &str = "var/lib/dpkg/status";
+
+/// Stability used for dpkg components.
+///
+/// Unlike RPM headers and ALPM `desc` files, dpkg's metadata carries neither a
+/// build timestamp nor a changelog, so there is nothing to feed the Poisson
+/// model in [`calculate_stability`]. We fall back to a neutral value that keeps
+/// dpkg components from being treated as especially stable or especially
+/// volatile until a richer signal is available.
+///
+/// [`calculate_stability`]: crate::utils::calculate_stability
+const FALLBACK_STABILITY: f64 = 0.5;
+
+/// dpkg has no per-package build timestamp, so there is nothing to clamp file
+/// mtimes down to. `u64::MAX` leaves every file's own mtime untouched.
+const NO_MTIME_CLAMP: u64 = u64::MAX;
+
+/// dpkg-based components repo implementation.
+///
+/// Uses the dpkg status database to determine file ownership and groups files
+/// by their source package, mirroring the way [`RpmRepo`] groups by SRPM.
+///
+/// [`RpmRepo`]: super::rpm::RpmRepo
+pub struct DpkgRepo {
+    /// Unique component (source package) names mapped to (mtime_clamp,
+    /// stability), indexed by ComponentId.
+    components: IndexMap<String, (u64, f64)>,
+
+    /// Mapping from path to list of (ComponentId, FileType).
+    ///
+    /// It's common for directories to be owned by more than one component (i.e.
+    /// from _different_ source packages). dpkg `.list` files don't record the
+    /// file type, so it is resolved by stat-ing the path in the rootfs and
+    /// stored here for [`claims_for_path`](Self::claims_for_path) to filter on.
+    path_to_components: HashMap<Utf8PathBuf, Vec<(ComponentId, FileType)>>,
+}
+
+impl DpkgRepo {
+    /// Load the dpkg database from the given rootfs. The `files` parameter is
+    /// used to canonicalize paths from the dpkg file lists.
+    ///
+    /// Returns `Ok(None)` if no dpkg status database is detected.
+    pub fn load(rootfs: &Dir, files: &FileMap, now: u64) -> Result<Option<Self>> {
+        if !rootfs
+            .try_exists(DPKG_STATUS)
+            .with_context(|| format!("checking for {DPKG_STATUS}"))?
+        {
+            return Ok(None);
+        }
+        Self::load_from_rootfs(rootfs, files, now).map(Some)
+    }
+
+    fn load_from_rootfs(rootfs: &Dir, files: &FileMap, now: u64) -> Result<Self> {
+        let _ = now;
+
+        let status = rootfs
+            .read_to_string(DPKG_STATUS)
+            .with_context(|| format!("reading {DPKG_STATUS}"))?;
+        let entries = parse_status(&status);
+
+        let info_dir = rootfs
+            .open_dir(DPKG_INFO_DIR)
+            .with_context(|| format!("opening {DPKG_INFO_DIR}"))?;
+
+        let mut components: IndexMap<String, (u64, f64)> = IndexMap::new();
+        let mut path_to_components: HashMap<Utf8PathBuf, Vec<(ComponentId, FileType)>> =
+            HashMap::new();
+        let mut cache = HashMap::new();
+
+        let package_count = entries.len();
+        for entry in &entries {
+            let component_name = source_package(&entry.source, &entry.package);
+
+            let component_entry = components.entry(component_name);
+            let component_id = ComponentId(component_entry.index());
+            component_entry.or_insert((NO_MTIME_CLAMP, FALLBACK_STABILITY));
+
+            // dpkg records an installed package's files in
+            // `<pkg>.list`, and under multiarch in `<pkg>:<arch>.list`. Config
+            // files are additionally listed in `<pkg>.conffiles`; we union them
+            // in so a conffile missing from the `.list` is still attributed.
+            let mut owned = read_list(&info_dir, &entry.package, entry.arch.as_deref())
+                .with_context(|| format!("reading file list for {}", entry.package))?;
+            owned.extend(
+                read_conffiles(&info_dir, &entry.package)
+                    .with_context(|| format!("reading conffiles for {}", entry.package))?,
+            );
+
+            for path in owned {
+                let canonical = canonicalize_parent_path(rootfs, files, &path, &mut cache)
+                    .with_context(|| format!("canonicalizing {path}"))?;
+
+                let file_type = match file_type_in_rootfs(rootfs, &canonical)
+                    .with_context(|| format!("stat-ing {canonical}"))?
+                {
+                    Some(ft) => ft,
+                    // The path is listed by dpkg but absent from (or unsupported
+                    // in) the rootfs; nothing for us to claim.
+                    None => continue,
+                };
+
+                let list = path_to_components.entry(canonical).or_default();
+                if !list.iter().any(|(id, _)| *id == component_id) {
+                    list.push((component_id, file_type));
+                }
+            }
+        }
+
+        tracing::debug!(
+            packages = package_count,
+            components = components.len(),
+            paths = path_to_components.len(),
+            "loaded dpkg database"
+        );
+
+        Ok(Self {
+            components,
+            path_to_components,
+        })
+    }
+}
+
+impl ComponentsRepo for DpkgRepo {
+    fn name(&self) -> &'static str {
+        REPO_NAME
+    }
+
+    fn default_priority(&self) -> usize {
+        10
+    }
+
+    fn claims_for_path(&self, path: &Utf8Path, file_type: FileType) -> Vec<ComponentId> {
+        // Don't claim the dpkg/apt database paths - let them fall into
+        // chunkah/unclaimed.
+        if let Ok(rel_path) = path.strip_prefix("/")
+            && DPKGDB_PATHS.iter().any(|p| rel_path.starts_with(p))
+        {
+            return Vec::new();
+        }
+
+        self.path_to_components
+            .get(path)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|(_, ft)| *ft == file_type)
+                    .map(|(id, _)| *id)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn component_info(&self, id: ComponentId) -> ComponentInfo<'_> {
+        let (name, (mtime, stability)) = self
+            .components
+            .get_index(id.0)
+            // SAFETY: the ids we're given come from the IndexMap itself when we
+            // inserted the element, so it must be valid.
+            .expect("invalid ComponentId");
+        ComponentInfo {
+            name,
+            mtime_clamp: *mtime,
+            stability: *stability,
+        }
+    }
+}
+
+/// A single installed-package record from the dpkg status database.
+struct StatusEntry {
+    package: String,
+    source: Option<String>,
+    arch: Option<String>,
+}
+
+/// Parse the dpkg `status` database into its installed-package records.
+///
+/// The file is a series of RFC-822-style stanzas separated by blank lines. We
+/// only care about the `Package`, `Source` and `Architecture` fields, and skip
+/// stanzas that aren't in the `installed` state (e.g. config-files remnants of
+/// a purged package).
+fn parse_status(content: &str) -> Vec<StatusEntry> {
+    let mut entries = Vec::new();
+    for stanza in content.split("\n\n") {
+        let mut package = None;
+        let mut source = None;
+        let mut arch = None;
+        let mut installed = false;
+
+        for line in stanza.lines() {
+            // Continuation lines start with whitespace; none of the fields we
+            // read are multi-line, so they can be ignored.
+            if line.starts_with([' ', '\t']) {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim();
+                match key.trim() {
+                    "Package" => package = Some(value.to_string()),
+                    "Source" => source = Some(value.to_string()),
+                    "Architecture" => arch = Some(value.to_string()),
+                    "Status" => installed = value.split_whitespace().last() == Some("installed"),
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(package) = package
+            && installed
+        {
+            entries.push(StatusEntry {
+                package,
+                source,
+                arch,
+            });
+        }
+    }
+    entries
+}
+
+/// Resolve the source package name for a binary package.
+///
+/// dpkg's `Source` field is either a bare name or `name (version)`; the version
+/// is stripped. When absent, the binary package name is used, exactly as
+/// `RpmRepo` falls back to the package name when the SRPM is missing.
+fn source_package(source: &Option<String>, package: &str) -> String {
+    match source {
+        Some(source) => source
+            .split_once(' ')
+            .map(|(name, _)| name)
+            .unwrap_or(source)
+            .to_string(),
+        None => package.to_string(),
+    }
+}
+
+/// Read and parse a package's `.list` file, trying the plain name first and the
+/// multiarch `<pkg>:<arch>.list` form second. A missing list is not an error —
+/// some packages (e.g. metapackages) own no files.
+fn read_list(info_dir: &Dir, package: &str, arch: Option<&str>) -> Result<Vec<Utf8PathBuf>> {
+    let mut names = vec![format!("{package}.list")];
+    if let Some(arch) = arch {
+        names.push(format!("{package}:{arch}.list"));
+    }
+    for name in names {
+        if let Some(content) = read_optional(info_dir, &name)? {
+            return Ok(parse_paths(&content));
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Read and parse a package's `.conffiles`, returning its paths. Absent for
+/// packages that ship no configuration files.
+fn read_conffiles(info_dir: &Dir, package: &str) -> Result<Vec<Utf8PathBuf>> {
+    match read_optional(info_dir, &format!("{package}.conffiles"))? {
+        Some(content) => Ok(parse_paths(&content)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Read a file under `dir`, returning `None` if it does not exist.
+fn read_optional(dir: &Dir, name: &str) -> Result<Option<String>> {
+    match dir.read_to_string(name) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("reading {name}")),
+    }
+}
+
+/// Parse the absolute paths from a dpkg `.list`/`.conffiles` file, one per line.
+///
+/// The root entry (`/.`) and empty lines are dropped; everything else is a
+/// package-owned path.
+fn parse_paths(content: &str) -> Vec<Utf8PathBuf> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && *line != "/." && *line != "/")
+        .map(Utf8PathBuf::from)
+        .collect()
+}
+
+/// Resolve the [`FileType`] of an absolute rootfs path by stat-ing it, without
+/// following a final symlink. Returns `None` for a path that is missing or of
+/// an unsupported type, mirroring `rpm::file_info_to_file_type`.
+fn file_type_in_rootfs(rootfs: &Dir, path: &Utf8Path) -> Result<Option<FileType>> {
+    let rel = path.strip_prefix("/").unwrap_or(path);
+    let metadata = match rootfs.symlink_metadata(rel.as_str()) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("getting metadata for {path}")),
+    };
+    let file_type = metadata.file_type();
+    Ok(FileType::from_cap_std(&file_type).or_else(|| {
+        use cap_std_ext::cap_std::fs::FileTypeExt;
+        if file_type.is_block_device() {
+            Some(FileType::BlockDevice)
+        } else if file_type.is_char_device() {
+            Some(FileType::CharDevice)
+        } else if file_type.is_fifo() {
+            Some(FileType::Fifo)
+        } else if file_type.is_socket() {
+            Some(FileType::Socket)
+        } else {
+            None
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "\
+Package: bash
+Status: install ok installed
+Architecture: amd64
+Version: 5.2.15-2ubuntu1
+Source: bash-src (5.2.15-2ubuntu1)
+
+Package: coreutils
+Status: install ok installed
+Architecture: amd64
+Version: 9.4-2
+
+Package: libc6
+Status: deinstall ok config-files
+Architecture: amd64
+Source: glibc
+";
+
+    #[test]
+    fn test_parse_status_installed_only() {
+        let entries = parse_status(STATUS);
+        // libc6 is in the config-files state, not installed, so it's dropped.
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].package, "bash");
+        assert_eq!(entries[0].source.as_deref(), Some("bash-src (5.2.15-2ubuntu1)"));
+        assert_eq!(entries[0].arch.as_deref(), Some("amd64"));
+        assert_eq!(entries[1].package, "coreutils");
+        assert_eq!(entries[1].source, None);
+    }
+
+    #[test]
+    fn test_source_package() {
+        // Source with a version is stripped down to the bare name.
+        assert_eq!(
+            source_package(&Some("bash-src (5.2.15-2ubuntu1)".to_string()), "bash"),
+            "bash-src"
+        );
+        // Source without a version is used verbatim.
+        assert_eq!(source_package(&Some("glibc".to_string()), "libc6"), "glibc");
+        // No Source field falls back to the binary package name.
+        assert_eq!(source_package(&None, "coreutils"), "coreutils");
+    }
+
+    #[test]
+    fn test_parse_paths() {
+        let content = "/.\n/usr\n/usr/bin\n/usr/bin/bash\n\n";
+        let paths = parse_paths(content);
+        assert_eq!(
+            paths,
+            vec![
+                Utf8PathBuf::from("/usr"),
+                Utf8PathBuf::from("/usr/bin"),
+                Utf8PathBuf::from("/usr/bin/bash"),
+            ]
+        );
+    }
+}
@@ -5,6 +5,8 @@ use camino::{Utf8Path, Utf8PathBuf};
 use cap_std_ext::cap_std::fs::Dir;
 use indexmap::IndexMap;
 use rpm_qa::FileInfo;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use crate::utils::{calculate_stability, canonicalize_parent_path};
 
@@ -14,6 +16,22 @@ const REPO_NAME: &str = "rpm";
 
 const RPMDB_PATHS: &[&str] = &["usr/lib/sysimage/rpm", "usr/share/rpm", "var/lib/rpm"];
 
+/// Candidate rpmdb file names within an [`RPMDB_PATHS`] directory, in the order
+/// rpm itself probes them (sqlite, ndb, then the legacy BDB `Packages`).
+const RPMDB_FILES: &[&str] = &["rpmdb.sqlite", "Packages.db", "Packages"];
+
+/// RPM file flag (`RPMFILE_CONFIG`): the path is a `%config` file, expected to
+/// diverge from the packaged content once edited locally.
+const RPMFILE_CONFIG: u32 = 1 << 0;
+/// RPM file flag (`RPMFILE_GHOST`): the path is owned but shipped empty, so RPM
+/// records no digest for it.
+const RPMFILE_GHOST: u32 = 1 << 6;
+
+/// PGP/RPM hash-algorithm number for SHA-256, the default when
+/// `RPMTAG_FILEDIGESTALGO` is unset (as on older images that only ever used
+/// SHA-256).
+const PGPHASHALGO_SHA256: u32 = 8;
+
 /// RPM-based components repo implementation.
 ///
 /// Uses the RPM database to determine file ownership and groups files
@@ -22,20 +40,107 @@ pub struct RpmRepo {
     /// Unique component (SRPM) names mapped to (buildtime, stability), indexed by ComponentId.
     components: IndexMap<String, (u64, f64)>,
 
-    /// Mapping from path to list of (ComponentId, FileInfo).
+    /// Mapping from path to list of (ComponentId, RpmFileInfo).
     ///
     /// It's common for directories to be owned by more than one component (i.e.
     /// from _different_ SRPMs). It's much more uncommon for files/symlinks
     /// though we do handle it to ensure reproducible layers.
-    path_to_components: HashMap<Utf8PathBuf, Vec<(ComponentId, FileInfo)>>,
+    path_to_components: HashMap<Utf8PathBuf, Vec<(ComponentId, RpmFileInfo)>>,
+
+    /// Regular-file paths whose on-disk content diverged from the digest RPM
+    /// recorded (see [`RpmRepo::verify`]). These are withheld from
+    /// [`claims_for_path`](Self::claims_for_path) so locally modified or
+    /// runtime-generated files fall into chunkah/unclaimed rather than the
+    /// stable component layer they would otherwise churn.
+    modified: std::collections::HashSet<Utf8PathBuf>,
+
+    /// Optional upper bound applied to every component's `mtime_clamp`, capping
+    /// per-component build timestamps at a single externally supplied epoch
+    /// (typically `SOURCE_DATE_EPOCH`) for bit-reproducible layers.
+    mtime_ceiling: Option<u64>,
+}
+
+/// Options for the `rpm -V`-style digest verification performed by
+/// [`RpmRepo::verify`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VerifyOptions {
+    /// Exempt `%config` files, which are legitimately expected to differ from
+    /// the packaged content and whose divergence is therefore not a signal that
+    /// the file should leave its component layer.
+    pub exempt_config: bool,
+}
+
+/// The subset of [`rpm_qa::FileInfo`] we retain per claimed path.
+///
+/// `FileInfo` itself is not `serde`-serializable, so persisting the computed
+/// mapping (see [`RpmRepo::load`]) means wrapping the fields we actually
+/// consume — the mode, the recorded digest and its algorithm, and the file
+/// flags — in a type we own and can round-trip.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RpmFileInfo {
+    /// Raw `st_mode`, carrying both the permission bits and the type bits we
+    /// match against in [`file_info_to_file_type`].
+    mode: u32,
+    /// Hex-encoded per-file digest recorded by RPM, or `None` for ghost files
+    /// which carry no recorded content.
+    digest: Option<String>,
+    /// RPM file flags (`RPMFILE_*`), consulted for `%config`/ghost status.
+    flags: u32,
+    /// `RPMTAG_FILEDIGESTALGO` of the owning package, defaulting to
+    /// [`PGPHASHALGO_SHA256`] when the tag is unset.
+    digest_algo: u32,
+}
+
+impl RpmFileInfo {
+    fn new(fi: &FileInfo, digest_algo: u32) -> Self {
+        Self {
+            mode: fi.mode as u32,
+            digest: fi.digest.clone(),
+            flags: fi.flags,
+            digest_algo,
+        }
+    }
+
+    fn is_config(&self) -> bool {
+        self.flags & RPMFILE_CONFIG != 0
+    }
+
+    fn is_ghost(&self) -> bool {
+        self.flags & RPMFILE_GHOST != 0
+    }
 }
 
 impl RpmRepo {
     /// Load the RPM database from the given rootfs. The `files` parameter is
     /// used to canonicalize paths from the RPM database.
     ///
+    /// When `cache` is `Some`, it names an on-disk cache file. Querying the
+    /// rpmdb and, above all, [`canonicalize_package_paths`] (which resolves
+    /// directory symlinks for every packaged path) is expensive and unchanged
+    /// from run to run. We therefore fingerprint the installed package set plus
+    /// the rpmdb file's size and mtime and, if a cache stamped with a matching
+    /// fingerprint exists, deserialize the fully-built mapping directly —
+    /// skipping canonicalization entirely. Otherwise we rebuild and rewrite the
+    /// cache. This mirrors the fingerprinted [`ScanCache`] used to avoid
+    /// redundant content hashing.
+    ///
+    /// When `verify` is `Some`, the on-disk content of every packaged regular
+    /// file is hashed and compared against the digest RPM recorded (see
+    /// [`Self::verify`]); divergent paths are withheld from `claims_for_path`.
+    /// Verification runs on both a cache hit and a fresh build since it depends
+    /// on rootfs content, not on the package set the cache is keyed by.
+    ///
     /// Returns `Ok(None)` if no RPM database is detected.
-    pub fn load(rootfs: &Dir, files: &super::FileMap, now: u64) -> Result<Option<Self>> {
+    ///
+    /// [`ScanCache`]: crate::scancache::ScanCache
+    pub fn load(
+        rootfs: &Dir,
+        files: &super::FileMap,
+        now: u64,
+        cache: Option<&Utf8Path>,
+        verify: Option<VerifyOptions>,
+        mtime_ceiling: Option<u64>,
+    ) -> Result<Option<Self>> {
         if !has_rpmdb(rootfs)? {
             return Ok(None);
         }
@@ -43,16 +148,105 @@ impl RpmRepo {
         let mut packages =
             rpm_qa::load_from_rootfs_dir(rootfs).context("loading rpmdb from rootfs")?;
 
+        // The fingerprint is computed from the raw (pre-canonicalization)
+        // packages so a cache hit lets us skip canonicalization too.
+        let fingerprint = match cache {
+            Some(_) => Some(compute_fingerprint(rootfs, &packages)?),
+            None => None,
+        };
+        if let (Some(cache_path), Some(fp)) = (cache, &fingerprint)
+            && let Some(repo) = Self::load_from_cache(cache_path, fp)
+                .with_context(|| format!("reading component cache {cache_path}"))?
+        {
+            tracing::debug!(cache = %cache_path, "reusing cached component mapping");
+            let mut repo = repo;
+            repo.mtime_ceiling = mtime_ceiling;
+            if let Some(opts) = verify {
+                repo.verify(rootfs, opts)?;
+            }
+            return Ok(Some(repo));
+        }
+
         tracing::debug!(packages = packages.len(), "canonicalizing package paths");
         canonicalize_package_paths(rootfs, files, &mut packages)
             .context("canonicalizing package paths")?;
 
-        Self::load_from_packages(packages, now).map(Some)
+        let mut repo = Self::load_from_packages(packages, now, mtime_ceiling)?;
+
+        if let (Some(cache_path), Some(fp)) = (cache, &fingerprint) {
+            repo.save_cache(cache_path, fp)
+                .with_context(|| format!("writing component cache {cache_path}"))?;
+        }
+
+        if let Some(opts) = verify {
+            repo.verify(rootfs, opts)?;
+        }
+
+        Ok(Some(repo))
     }
 
-    pub fn load_from_packages(packages: rpm_qa::Packages, now: u64) -> Result<Self> {
+    /// Populate [`Self::modified`] with the packaged regular files whose on-disk
+    /// content differs from the digest recorded in the RPM header, `rpm -V`
+    /// style.
+    ///
+    /// Ghost files (no recorded digest) carry no content to check and are
+    /// skipped, as are `%config` files when [`VerifyOptions::exempt_config`] is
+    /// set. The hash function is chosen from each file's recorded algorithm; a
+    /// file digested with an algorithm we can't compute is left claimed rather
+    /// than guessed modified.
+    fn verify(&mut self, rootfs: &Dir, opts: VerifyOptions) -> Result<()> {
+        let mut checked = 0usize;
+        for (path, entries) in &self.path_to_components {
+            // A path's regular-file claims all describe the same byte stream, so
+            // the first one with a recorded digest is enough to verify against.
+            let Some(fi) = entries
+                .iter()
+                .map(|(_, fi)| fi)
+                .find(|fi| {
+                    file_info_to_file_type(fi) == Some(FileType::File) && fi.digest.is_some()
+                })
+            else {
+                continue;
+            };
+            if fi.is_ghost() || fi.digest.is_none() {
+                continue;
+            }
+            if opts.exempt_config && fi.is_config() {
+                continue;
+            }
+            let recorded = fi.digest.as_deref().unwrap();
+
+            let rel = path.strip_prefix("/").unwrap_or(path);
+            let on_disk = match hash_file(rootfs, rel.as_str(), fi.digest_algo)
+                .with_context(|| format!("verifying {path}"))?
+            {
+                Some(digest) => digest,
+                // Missing file or unsupported algorithm: don't claim it modified.
+                None => continue,
+            };
+
+            checked += 1;
+            if !on_disk.eq_ignore_ascii_case(recorded) {
+                tracing::trace!(path = %path, "on-disk digest diverges; withholding claim");
+                self.modified.insert(path.clone());
+            }
+        }
+
+        tracing::debug!(
+            checked,
+            modified = self.modified.len(),
+            "verified packaged files against recorded digests"
+        );
+        Ok(())
+    }
+
+    pub fn load_from_packages(
+        packages: rpm_qa::Packages,
+        now: u64,
+        mtime_ceiling: Option<u64>,
+    ) -> Result<Self> {
         let mut components: IndexMap<String, (u64, f64)> = IndexMap::new();
-        let mut path_to_components: HashMap<Utf8PathBuf, Vec<(ComponentId, FileInfo)>> =
+        let mut path_to_components: HashMap<Utf8PathBuf, Vec<(ComponentId, RpmFileInfo)>> =
             HashMap::new();
 
         let package_count = packages.len();
@@ -82,13 +276,16 @@ impl RpmRepo {
                 }
             }
 
+            // The file-digest algorithm is recorded once per package header.
+            let digest_algo = pkg.file_digest_algo.unwrap_or(PGPHASHALGO_SHA256);
+
             for (path, file_info) in pkg.files.into_iter() {
                 // Accumulate entries for all file types. Skip if this component
                 // already owns this path (can happen when multiple subpackages
                 // from the same SRPM own the same path).
                 let entries = path_to_components.entry(path).or_default();
                 if !entries.iter().any(|(id, _)| *id == component_id) {
-                    entries.push((component_id, file_info));
+                    entries.push((component_id, RpmFileInfo::new(&file_info, digest_algo)));
                 }
             }
         }
@@ -103,8 +300,120 @@ impl RpmRepo {
         Ok(Self {
             components,
             path_to_components,
+            modified: std::collections::HashSet::new(),
+            mtime_ceiling,
         })
     }
+
+    /// Load a cached mapping from `path`, returning `Ok(None)` when the file is
+    /// absent, unreadable, written by an incompatible version, or stamped with a
+    /// fingerprint that doesn't match `fingerprint`.
+    fn load_from_cache(path: &Utf8Path, fingerprint: &str) -> Result<Option<Self>> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("reading cache"),
+        };
+
+        let file: CacheFile = match serde_json::from_slice(&bytes) {
+            Ok(file) => file,
+            // A corrupt or truncated cache is never fatal; just rebuild.
+            Err(e) => {
+                tracing::warn!(error = %e, path = %path, "ignoring unreadable component cache");
+                return Ok(None);
+            }
+        };
+
+        if file.version != CACHE_VERSION || file.fingerprint != fingerprint {
+            tracing::debug!(path = %path, "component cache miss");
+            return Ok(None);
+        }
+
+        // The position of each entry in `components` is its ComponentId, so the
+        // insertion order recorded in the cache must be preserved to keep the
+        // ids the paths refer to valid for `component_info`/`get_index`.
+        let components: IndexMap<String, (u64, f64)> = file
+            .components
+            .into_iter()
+            .map(|(name, buildtime, stability)| (name, (buildtime, stability)))
+            .collect();
+
+        let path_to_components = file
+            .paths
+            .into_iter()
+            .map(|(path, entries)| {
+                let entries = entries
+                    .into_iter()
+                    .map(|(id, fi)| (ComponentId(id), fi))
+                    .collect();
+                (path, entries)
+            })
+            .collect();
+
+        Ok(Some(Self {
+            components,
+            path_to_components,
+            modified: std::collections::HashSet::new(),
+            // Set by the caller (`load`) after construction; the ceiling is an
+            // external build parameter, not part of the cached mapping.
+            mtime_ceiling: None,
+        }))
+    }
+
+    /// Serialize the built mapping to `path`, stamped with `fingerprint`.
+    fn save_cache(&self, path: &Utf8Path, fingerprint: &str) -> Result<()> {
+        let components = self
+            .components
+            .iter()
+            .map(|(name, (buildtime, stability))| (name.clone(), *buildtime, *stability))
+            .collect();
+
+        let paths = self
+            .path_to_components
+            .iter()
+            .map(|(path, entries)| {
+                let entries = entries
+                    .iter()
+                    .map(|(id, fi)| (id.0, fi.clone()))
+                    .collect();
+                (path.clone(), entries)
+            })
+            .collect();
+
+        let file = CacheFile {
+            version: CACHE_VERSION,
+            fingerprint: fingerprint.to_string(),
+            components,
+            paths,
+        };
+        let bytes = serde_json::to_vec(&file).context("serializing component cache")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating component cache directory {parent}"))?;
+        }
+        std::fs::write(path, bytes).context("writing cache")?;
+        Ok(())
+    }
+}
+
+/// On-disk format version. Bumped whenever the serialized layout changes so a
+/// stale cache is discarded rather than misinterpreted.
+const CACHE_VERSION: u32 = 2;
+
+/// Serialized form of a fully-built [`RpmRepo`] mapping.
+///
+/// `components` is stored as an ordered list rather than a map: its position is
+/// the [`ComponentId`], so insertion order is load-bearing and must survive the
+/// round-trip. Paths store the raw `usize` of each `ComponentId` alongside the
+/// [`RpmFileInfo`] wrapper.
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    /// Fingerprint of the installed package set and rpmdb file this mapping was
+    /// computed from; a mismatch invalidates the cache.
+    fingerprint: String,
+    components: Vec<(String, u64, f64)>,
+    paths: Vec<(Utf8PathBuf, Vec<(usize, RpmFileInfo)>)>,
 }
 
 impl ComponentsRepo for RpmRepo {
@@ -124,6 +433,13 @@ impl ComponentsRepo for RpmRepo {
             return Vec::new();
         }
 
+        // A regular file whose content diverged from the packaged digest is
+        // withheld so it falls into chunkah/unclaimed instead of churning the
+        // stable component layer.
+        if file_type == FileType::File && self.modified.contains(path) {
+            return Vec::new();
+        }
+
         self.path_to_components
             .get(path)
             .map(|entries| {
@@ -143,9 +459,16 @@ impl ComponentsRepo for RpmRepo {
             // SAFETY: the ids we're given come from the IndexMap itself when we
             // inserted the element, so it must be valid.
             .expect("invalid ComponentId");
+        // Cap the per-component build timestamp at the global ceiling, if any,
+        // so no emitted file mtime exceeds the chosen epoch. The stability
+        // ordering is derived separately and is unaffected.
+        let mtime_clamp = match self.mtime_ceiling {
+            Some(ceiling) => (*mtime).min(ceiling),
+            None => *mtime,
+        };
         ComponentInfo {
             name,
-            mtime_clamp: *mtime,
+            mtime_clamp,
             stability: *stability,
         }
     }
@@ -166,6 +489,137 @@ fn has_rpmdb(rootfs: &Dir) -> anyhow::Result<bool> {
     Ok(false)
 }
 
+/// Locate the rpmdb file within the detected database directory.
+fn detect_rpmdb_file(rootfs: &Dir) -> Result<Option<Utf8PathBuf>> {
+    for dir in RPMDB_PATHS {
+        for file in RPMDB_FILES {
+            let path = format!("{dir}/{file}");
+            if rootfs
+                .try_exists(&path)
+                .with_context(|| format!("checking for {path}"))?
+            {
+                return Ok(Some(Utf8PathBuf::from(path)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Fingerprint the installed package set and the rpmdb file.
+///
+/// The hash covers the sorted `(name, version-release, buildtime)` of every
+/// package plus the size and mtime of the detected rpmdb file. Any install,
+/// upgrade or removal changes a package triple; an in-place database edit that
+/// somehow left the triples untouched still moves the file's size or mtime.
+fn compute_fingerprint(rootfs: &Dir, packages: &rpm_qa::Packages) -> Result<String> {
+    let mut triples: Vec<(String, String, u64)> = packages
+        .values()
+        .map(|pkg| {
+            (
+                pkg.name.clone(),
+                format!("{}-{}", pkg.version, pkg.release),
+                pkg.buildtime,
+            )
+        })
+        .collect();
+    triples.sort();
+
+    let mut hasher = Sha256::new();
+    for (name, vr, buildtime) in &triples {
+        hasher.update(name.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(vr.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(buildtime.to_le_bytes());
+        hasher.update(b"\0");
+    }
+
+    if let Some(db) = detect_rpmdb_file(rootfs)? {
+        let metadata = rootfs
+            .metadata(db.as_str())
+            .with_context(|| format!("stat-ing {db}"))?;
+        hasher.update(metadata.len().to_le_bytes());
+        if let Ok(mtime) = metadata.modified()
+            && let Ok(dur) = mtime.into_std().duration_since(std::time::UNIX_EPOCH)
+        {
+            hasher.update(dur.as_secs().to_le_bytes());
+            hasher.update(dur.subsec_nanos().to_le_bytes());
+        }
+    }
+
+    Ok(hex_encode(&hasher.finalize()))
+}
+
+/// Hash the regular file at `rel` under `rootfs` with the RPM
+/// file-digest-algorithm `algo`, returning its lowercase hex digest.
+///
+/// Returns `Ok(None)` when the file is absent (an owned path can legitimately
+/// be gone) or when `algo` names a hash we don't implement, so the caller
+/// leaves such a file claimed rather than guessing it modified. Only the
+/// SHA-2 family is supported, which covers every digest modern RPM records.
+fn hash_file(rootfs: &Dir, rel: &str, algo: u32) -> Result<Option<String>> {
+    use std::io::Read;
+
+    // PGP/RPM hash-algorithm numbers, per RFC 4880.
+    const PGPHASHALGO_SHA384: u32 = 9;
+    const PGPHASHALGO_SHA512: u32 = 10;
+    const PGPHASHALGO_SHA224: u32 = 11;
+
+    // Stat the node before opening it. Only regular files carry content to
+    // hash; a special node (device, fifo, socket) recorded by RPM has no digest
+    // to compare against. Crucially this check must happen *before* the open: an
+    // `O_RDONLY` open of a FIFO blocks until a writer appears, so a post-open
+    // guard would never be reached. `symlink_metadata` also avoids following a
+    // dangling or special-target symlink into such a node.
+    match rootfs.symlink_metadata(rel) {
+        Ok(meta) if meta.is_file() => {}
+        Ok(_) => return Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("stat {rel}")),
+    }
+
+    let mut file = match rootfs.open(rel) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("opening {rel}")),
+    };
+
+    fn digest<D: Digest>(file: &mut impl std::io::Read) -> Result<String> {
+        let mut hasher = D::new();
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf).context("reading file")?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(hex_encode(hasher.finalize().as_slice()))
+    }
+
+    let hex = match algo {
+        PGPHASHALGO_SHA256 => digest::<Sha256>(&mut file)?,
+        PGPHASHALGO_SHA224 => digest::<sha2::Sha224>(&mut file)?,
+        PGPHASHALGO_SHA384 => digest::<sha2::Sha384>(&mut file)?,
+        PGPHASHALGO_SHA512 => digest::<sha2::Sha512>(&mut file)?,
+        other => {
+            tracing::debug!(algo = other, path = rel, "unsupported file-digest algorithm");
+            return Ok(None);
+        }
+    };
+    Ok(Some(hex))
+}
+
+/// Hex-encode a byte slice (lowercase), for the cache fingerprint.
+fn hex_encode(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(s, "{b:02x}");
+    }
+    s
+}
+
 /// Canonicalize all file paths in packages by resolving directory symlinks.
 fn canonicalize_package_paths(
     rootfs: &Dir,
@@ -206,12 +660,19 @@ fn parse_srpm_name(srpm: &str) -> &str {
     }
 }
 
-fn file_info_to_file_type(fi: &FileInfo) -> Option<FileType> {
+fn file_info_to_file_type(fi: &RpmFileInfo) -> Option<FileType> {
     let file_type = (fi.mode as libc::mode_t) & libc::S_IFMT;
     match file_type {
         libc::S_IFDIR => Some(FileType::Directory),
         libc::S_IFREG => Some(FileType::File),
         libc::S_IFLNK => Some(FileType::Symlink),
+        // Special (non-regular) nodes carry no content but are still real
+        // entries that the packer must claim and place, so classify them
+        // explicitly rather than dropping them as unsupported.
+        libc::S_IFBLK => Some(FileType::BlockDevice),
+        libc::S_IFCHR => Some(FileType::CharDevice),
+        libc::S_IFIFO => Some(FileType::Fifo),
+        libc::S_IFSOCK => Some(FileType::Socket),
         _ => None,
     }
 }
@@ -261,7 +722,7 @@ mod tests {
     #[test]
     fn test_claims_for_path() {
         let packages = rpm_qa::load_from_str(FIXTURE).unwrap();
-        let repo = RpmRepo::load_from_packages(packages, now_secs()).unwrap();
+        let repo = RpmRepo::load_from_packages(packages, now_secs(), None).unwrap();
 
         // /usr/bin/bash is a file owned by bash
         let claims = repo.claims_for_path(Utf8Path::new("/usr/bin/bash"), FileType::File);
@@ -305,7 +766,7 @@ mod tests {
     #[test]
     fn test_claims_for_path_wrong_type() {
         let packages = rpm_qa::load_from_str(FIXTURE).unwrap();
-        let repo = RpmRepo::load_from_packages(packages, now_secs()).unwrap();
+        let repo = RpmRepo::load_from_packages(packages, now_secs(), None).unwrap();
 
         // /usr/bin/bash is a file in RPM, but we query as symlink
         let claims = repo.claims_for_path(Utf8Path::new("/usr/bin/bash"), FileType::Symlink);
@@ -319,7 +780,7 @@ mod tests {
     #[test]
     fn test_shared_directories_claimed_by_multiple_components() {
         let packages = rpm_qa::load_from_str(FIXTURE).unwrap();
-        let repo = RpmRepo::load_from_packages(packages, now_secs()).unwrap();
+        let repo = RpmRepo::load_from_packages(packages, now_secs(), None).unwrap();
 
         // /usr/lib/.build-id is a well-known directory shared by many packages
         let claims = repo.claims_for_path(Utf8Path::new("/usr/lib/.build-id"), FileType::Directory);
@@ -338,6 +799,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cache_roundtrip_and_fingerprint() {
+        let packages = rpm_qa::load_from_str(FIXTURE).unwrap();
+        let repo = RpmRepo::load_from_packages(packages, now_secs(), None).unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::try_from(tmp.path().join("components.json")).unwrap();
+        repo.save_cache(&path, "fp-abc").unwrap();
+
+        // A matching fingerprint restores a mapping that resolves the same
+        // claims, with ComponentIds still pointing at the right components.
+        let cached = RpmRepo::load_from_cache(&path, "fp-abc").unwrap().unwrap();
+        let claims = cached.claims_for_path(Utf8Path::new("/usr/bin/bash"), FileType::File);
+        assert_eq!(claims.len(), 1);
+        assert_eq!(cached.component_info(claims[0]).name, "bash");
+
+        // A different fingerprint is a miss.
+        assert!(RpmRepo::load_from_cache(&path, "fp-xyz").unwrap().is_none());
+
+        // A missing cache file is a miss, not an error.
+        let missing = Utf8PathBuf::try_from(tmp.path().join("absent.json")).unwrap();
+        assert!(RpmRepo::load_from_cache(&missing, "fp-abc").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_mtime_ceiling_caps_clamp() {
+        let packages = rpm_qa::load_from_str(FIXTURE).unwrap();
+
+        // Without a ceiling, the clamp is the component's own build time.
+        let repo = RpmRepo::load_from_packages(packages, now_secs(), None).unwrap();
+        let claims = repo.claims_for_path(Utf8Path::new("/usr/bin/bash"), FileType::File);
+        assert_eq!(repo.component_info(claims[0]).mtime_clamp, 1753299195);
+
+        // A ceiling below the build time caps it; one above leaves it untouched.
+        let packages = rpm_qa::load_from_str(FIXTURE).unwrap();
+        let repo = RpmRepo::load_from_packages(packages, now_secs(), Some(1_700_000_000)).unwrap();
+        let claims = repo.claims_for_path(Utf8Path::new("/usr/bin/bash"), FileType::File);
+        assert_eq!(repo.component_info(claims[0]).mtime_clamp, 1_700_000_000);
+
+        let packages = rpm_qa::load_from_str(FIXTURE).unwrap();
+        let repo = RpmRepo::load_from_packages(packages, now_secs(), Some(2_000_000_000)).unwrap();
+        let claims = repo.claims_for_path(Utf8Path::new("/usr/bin/bash"), FileType::File);
+        assert_eq!(repo.component_info(claims[0]).mtime_clamp, 1753299195);
+    }
+
+    #[test]
+    fn test_modified_file_is_withheld() {
+        let packages = rpm_qa::load_from_str(FIXTURE).unwrap();
+        let mut repo = RpmRepo::load_from_packages(packages, now_secs(), None).unwrap();
+
+        // Before verification, /usr/bin/bash is claimed by bash.
+        let path = Utf8Path::new("/usr/bin/bash");
+        assert_eq!(repo.claims_for_path(path, FileType::File).len(), 1);
+
+        // Marking it modified withholds the file claim, letting it fall through
+        // to chunkah/unclaimed.
+        repo.modified.insert(path.to_owned());
+        assert!(repo.claims_for_path(path, FileType::File).is_empty());
+
+        // Only the regular-file claim is withheld; marking a symlink's path
+        // modified leaves its symlink claim intact, as the set is file-content
+        // specific.
+        let sh = Utf8Path::new("/usr/bin/sh");
+        repo.modified.insert(sh.to_owned());
+        assert_eq!(repo.claims_for_path(sh, FileType::Symlink).len(), 1);
+    }
+
     #[test]
     fn test_load_from_rpmdb_sqlite() {
         use std::process::Command;
@@ -360,7 +888,9 @@ mod tests {
         let rootfs = Dir::open_ambient_dir(tmp.path(), ambient_authority()).unwrap();
 
         let files = crate::scan::Scanner::new(&rootfs).scan().unwrap();
-        let repo = RpmRepo::load(&rootfs, &files, now_secs()).unwrap().unwrap();
+        let repo = RpmRepo::load(&rootfs, &files, now_secs(), None, None, None)
+            .unwrap()
+            .unwrap();
 
         // Test that paths we know are in filesystem and setup are claimed
         let claims = repo.claims_for_path(Utf8Path::new("/"), FileType::Directory);
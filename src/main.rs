@@ -1,9 +1,14 @@
 mod cmd_build;
 mod components;
 mod ocibuilder;
+mod matcher;
 #[allow(dead_code)]
 mod packing;
 mod scan;
+mod scancache;
+mod selinux;
+#[allow(dead_code)]
+mod stability;
 mod tar;
 mod utils;
 
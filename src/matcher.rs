@@ -0,0 +1,299 @@
+//! Path matchers for restricting a scan (and the claim pipeline) to a subtree.
+//!
+//! A [`Matcher`] carries a set of include and exclude patterns. When includes
+//! are present, only paths under an included pattern are visited; excludes
+//! subtract from whatever is included. Patterns are absolute and support `*`
+//! (matches within a path component) and `**` (matches across components), plus
+//! plain prefixes.
+//!
+//! Following dirstate's "only visit parts of the tree requested by the matcher"
+//! approach, the scanner asks the matcher what to do with each directory via
+//! [`Matcher::visit_children`], which returns a [`VisitChildrenSet`] so that
+//! subtrees which cannot contain any included path are pruned entirely rather
+//! than scanned and filtered afterwards.
+
+use std::collections::BTreeSet;
+
+use camino::Utf8Path;
+
+/// Decision for how to descend into a directory's children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VisitChildrenSet {
+    /// No child can contain a match; skip the directory entirely.
+    Empty,
+    /// Every child may contain a match; recurse without restriction.
+    All,
+    /// Only these immediate child names can lead to a match.
+    Set(BTreeSet<String>),
+}
+
+/// A single absolute glob pattern, decomposed into components.
+#[derive(Debug, Clone)]
+struct Pattern {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// A literal path component.
+    Literal(String),
+    /// `*` — matches any single component.
+    Star,
+    /// `**` — matches zero or more components.
+    DoubleStar,
+}
+
+impl Pattern {
+    fn parse(pat: &str) -> Self {
+        let trimmed = pat.strip_prefix('/').unwrap_or(pat);
+        let segments = trimmed
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| match s {
+                "**" => Segment::DoubleStar,
+                "*" => Segment::Star,
+                other => Segment::Literal(other.to_string()),
+            })
+            .collect();
+        Self { segments }
+    }
+
+    /// The leading run of literal components, i.e. the glob-free prefix.
+    fn literal_prefix(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .take_while(|s| matches!(s, Segment::Literal(_)))
+            .map(|s| match s {
+                Segment::Literal(l) => l.as_str(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    /// Whether this pattern matches `path` exactly (as a full match).
+    fn matches(&self, path: &[&str]) -> bool {
+        matches_segments(&self.segments, path)
+    }
+
+    /// Whether `path` is an ancestor of (or equal to a prefix of) something this
+    /// pattern could match — used to decide whether to keep descending.
+    fn could_match_under(&self, path: &[&str]) -> bool {
+        could_match_under(&self.segments, path)
+    }
+
+    /// Whether `path` itself matches, or sits beneath a directory that matches
+    /// (subtree semantics). `/usr/share/doc` matching implies every path under
+    /// it matches too.
+    fn matches_at_or_under(&self, path: &[&str]) -> bool {
+        (1..=path.len()).any(|n| self.matches(&path[..n]))
+    }
+}
+
+/// Glob match of `segs` against the full component list `path`.
+fn matches_segments(segs: &[Segment], path: &[&str]) -> bool {
+    match segs.first() {
+        None => path.is_empty(),
+        Some(Segment::DoubleStar) => {
+            // `**` consumes zero or more components.
+            (0..=path.len()).any(|skip| matches_segments(&segs[1..], &path[skip..]))
+        }
+        Some(Segment::Star) => {
+            !path.is_empty() && matches_segments(&segs[1..], &path[1..])
+        }
+        Some(Segment::Literal(lit)) => {
+            !path.is_empty() && path[0] == lit && matches_segments(&segs[1..], &path[1..])
+        }
+    }
+}
+
+/// Whether some extension of `path` could still match `segs`, i.e. `path`
+/// matches a prefix of the pattern.
+fn could_match_under(segs: &[Segment], path: &[&str]) -> bool {
+    match (segs.first(), path.first()) {
+        // Ran out of path: everything so far lined up, so descending may match.
+        (_, None) => true,
+        // Ran out of pattern but path continues: no match possible.
+        (None, Some(_)) => false,
+        (Some(Segment::DoubleStar), _) => {
+            // `**` can absorb this component, or be skipped.
+            could_match_under(&segs[1..], path) || could_match_under(segs, &path[1..])
+        }
+        (Some(Segment::Star), Some(_)) => could_match_under(&segs[1..], &path[1..]),
+        (Some(Segment::Literal(lit)), Some(c)) => {
+            c == lit && could_match_under(&segs[1..], &path[1..])
+        }
+    }
+}
+
+/// A compiled set of include/exclude patterns.
+#[derive(Debug, Clone, Default)]
+pub struct Matcher {
+    includes: Vec<Pattern>,
+    excludes: Vec<Pattern>,
+}
+
+impl Matcher {
+    /// Build a matcher from include and exclude pattern strings.
+    pub fn new<I, E>(includes: I, excludes: E) -> Self
+    where
+        I: IntoIterator<Item = String>,
+        E: IntoIterator<Item = String>,
+    {
+        Self {
+            includes: includes.into_iter().map(|p| Pattern::parse(&p)).collect(),
+            excludes: excludes.into_iter().map(|p| Pattern::parse(&p)).collect(),
+        }
+    }
+
+    /// True if this matcher restricts nothing (matches the whole tree).
+    pub fn is_trivial(&self) -> bool {
+        self.includes.is_empty() && self.excludes.is_empty()
+    }
+
+    /// Whether `path` (absolute) is selected by this matcher.
+    pub fn matches(&self, path: &Utf8Path) -> bool {
+        let comps = components(path);
+
+        // A path is included when it is at or below a full match (subtree
+        // semantics, honoring any glob tail) or when it is an ancestor leading
+        // toward a potential match, so the directories above an included
+        // subtree are retained for the layer.
+        let included = self.includes.is_empty()
+            || self
+                .includes
+                .iter()
+                .any(|p| p.matches_at_or_under(&comps) || p.could_match_under(&comps));
+        if !included {
+            return false;
+        }
+
+        // Excludes are subtree-semantic too: excluding `/var/cache` drops the
+        // directory and everything beneath it, symmetric with includes.
+        !self.excludes.iter().any(|p| p.matches_at_or_under(&comps))
+    }
+
+    /// Decide how to descend into the directory at absolute path `dir`.
+    pub fn visit_children(&self, dir: &Utf8Path) -> VisitChildrenSet {
+        let comps = components(dir);
+
+        // With no includes, the whole tree is in scope (excludes are applied
+        // per-entry in `matches`).
+        if self.includes.is_empty() {
+            return VisitChildrenSet::All;
+        }
+
+        let mut names: BTreeSet<String> = BTreeSet::new();
+        for pat in &self.includes {
+            let prefix = pat.literal_prefix();
+            if comps.len() >= prefix.len() {
+                // We're at or below the glob-free part of this include; once
+                // inside, anything underneath may match.
+                if pat.matches_at_or_under(&comps) || pat.could_match_under(&comps) {
+                    return VisitChildrenSet::All;
+                }
+            } else if prefix
+                .iter()
+                .zip(&comps)
+                .all(|(p, c)| *p == *c)
+            {
+                // Still walking the literal prefix toward the include; only the
+                // next named component is worth descending into.
+                names.insert(prefix[comps.len()].to_string());
+            }
+        }
+
+        if names.is_empty() {
+            VisitChildrenSet::Empty
+        } else {
+            VisitChildrenSet::Set(names)
+        }
+    }
+}
+
+fn components(path: &Utf8Path) -> Vec<&str> {
+    path.as_str()
+        .trim_start_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_include_prefix() {
+        let m = Matcher::new(["/usr/bin".to_string()], []);
+        assert!(m.matches(Utf8Path::new("/usr/bin/bash")));
+        assert!(m.matches(Utf8Path::new("/usr/bin")));
+        // Ancestors of an include are kept so the scan can reach it.
+        assert!(m.matches(Utf8Path::new("/usr")));
+        assert!(!m.matches(Utf8Path::new("/etc/passwd")));
+    }
+
+    #[test]
+    fn test_exclude() {
+        let m = Matcher::new([], ["/var/cache/**".to_string()]);
+        assert!(m.matches(Utf8Path::new("/usr/bin/bash")));
+        assert!(!m.matches(Utf8Path::new("/var/cache/dnf/x")));
+    }
+
+    #[test]
+    fn test_glob() {
+        let m = Matcher::new(["/usr/*/doc".to_string()], []);
+        let comps: Vec<&str> = vec!["usr", "share", "doc"];
+        assert!(m.includes[0].matches(&comps));
+        assert!(!m.includes[0].matches(&["usr", "a", "b", "doc"]));
+    }
+
+    #[test]
+    fn test_glob_include_honors_tail() {
+        let m = Matcher::new(["/usr/*/doc".to_string()], []);
+        // The glob tail is honored: only `doc` directories one level under /usr.
+        assert!(m.matches(Utf8Path::new("/usr/share/doc")));
+        assert!(m.matches(Utf8Path::new("/usr/share/doc/readme")));
+        // A sibling subtree under /usr that does not match the tail is excluded.
+        assert!(!m.matches(Utf8Path::new("/usr/share/man/page")));
+        // Ancestors leading toward the match are still kept for the layer.
+        assert!(m.matches(Utf8Path::new("/usr")));
+        assert!(m.matches(Utf8Path::new("/usr/share")));
+    }
+
+    #[test]
+    fn test_exclude_is_subtree() {
+        let m = Matcher::new([], ["/var/cache".to_string()]);
+        // Excluding a directory drops its contents too, not just the node.
+        assert!(!m.matches(Utf8Path::new("/var/cache")));
+        assert!(!m.matches(Utf8Path::new("/var/cache/dnf/x")));
+        assert!(m.matches(Utf8Path::new("/var/lib/rpm")));
+    }
+
+    #[test]
+    fn test_visit_children_pruning() {
+        let m = Matcher::new(["/usr/bin".to_string()], []);
+
+        // From the root, only /usr is worth descending into.
+        assert_eq!(
+            m.visit_children(Utf8Path::new("/")),
+            VisitChildrenSet::Set(["usr".to_string()].into_iter().collect())
+        );
+        // Inside /usr, only bin.
+        assert_eq!(
+            m.visit_children(Utf8Path::new("/usr")),
+            VisitChildrenSet::Set(["bin".to_string()].into_iter().collect())
+        );
+        // Inside the included subtree, everything.
+        assert_eq!(m.visit_children(Utf8Path::new("/usr/bin")), VisitChildrenSet::All);
+        assert_eq!(m.visit_children(Utf8Path::new("/usr/bin/sub")), VisitChildrenSet::All);
+        // A sibling subtree is pruned entirely.
+        assert_eq!(m.visit_children(Utf8Path::new("/etc")), VisitChildrenSet::Empty);
+    }
+
+    #[test]
+    fn test_trivial_visits_all() {
+        let m = Matcher::default();
+        assert!(m.is_trivial());
+        assert_eq!(m.visit_children(Utf8Path::new("/etc")), VisitChildrenSet::All);
+    }
+}
@@ -1,70 +1,523 @@
-use std::collections::BTreeMap;
-use std::ops::ControlFlow;
-use std::path::Path;
+use std::collections::HashMap;
 
 use anyhow::{Context, Result};
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use cap_std::fs::Dir;
-use cap_std_ext::dirext::{CapStdExtDirExt, WalkConfiguration};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
 
 use crate::components::{FileInfo, FileMap, FileType};
+use crate::matcher::{Matcher, VisitChildrenSet};
+use crate::scancache::{CacheEntry, ScanCache};
+use crate::selinux::FileContexts;
+use crate::utils::get_current_epoch;
 
-/// Scan the rootfs and return a map of file paths to their metadata.
-/// We use cap-std-ext's walk here, which doesn't follow symlinks.
+/// Upper bound on the number of worker threads used to walk the rootfs.
 ///
-/// If `skip_special_files` is true, special file types (sockets, FIFOs,
-/// block/char devices) are silently skipped. Otherwise, an error is returned.
-pub fn scan_rootfs(rootfs: &Dir, skip_special_files: bool) -> Result<FileMap> {
-    let mut files = BTreeMap::new();
-
-    let config = WalkConfiguration::default().path_base(Path::new("/"));
-
-    rootfs
-        .walk(&config, |component| {
-            let path: &Utf8Path = component
-                .path
-                .try_into()
-                .map_err(|_| anyhow::anyhow!("path is not valid UTF-8"))?;
-
-            let rel_path = path.strip_prefix("/").unwrap_or(path);
-            let fs_path = if rel_path.as_str().is_empty() {
-                "."
+/// Scanning is dominated by filesystem metadata calls (stat, listxattr) rather
+/// than CPU work, so beyond a handful of threads we stop being CPU-bound and
+/// start contending on kernel/IO locks. On many-core hosts unbounded rayon
+/// parallelism actually regresses throughput, so we cap it here.
+const MAX_SCAN_THREADS: usize = 16;
+
+/// Walks a rootfs and builds a [`FileMap`] of paths to their metadata.
+///
+/// The walk does not follow symlinks. Traversal is parallelized across a
+/// bounded pool of workers (see [`MAX_SCAN_THREADS`]); each worker builds a
+/// partial [`FileMap`] for the subtree it visits and the pieces are reduced
+/// into the final map at the end. Because [`FileInfo`] carries the inode and
+/// link count, the merged map preserves the complete inode picture that
+/// `BigfilesRepo::load` relies on to co-locate hardlinked files.
+pub struct Scanner<'a> {
+    rootfs: &'a Dir,
+    skip_special_files: bool,
+    prune: Vec<Utf8PathBuf>,
+    matcher: Matcher,
+    selinux: Option<FileContexts>,
+    no_sparse: bool,
+    cache: Option<Utf8PathBuf>,
+}
+
+impl<'a> Scanner<'a> {
+    /// Create a scanner for `rootfs`.
+    pub fn new(rootfs: &'a Dir) -> Self {
+        Self {
+            rootfs,
+            skip_special_files: false,
+            prune: Vec::new(),
+            matcher: Matcher::default(),
+            selinux: None,
+            no_sparse: false,
+            cache: None,
+        }
+    }
+
+    /// Disable sparse-file probing, forcing dense output for every file.
+    pub fn no_sparse(mut self, no_sparse: bool) -> Self {
+        self.no_sparse = no_sparse;
+        self
+    }
+
+    /// Synthesize `security.selinux` labels from a policy `file_contexts`
+    /// database instead of relying on the container runtime.
+    pub fn selinux_policy(mut self, policy: Option<FileContexts>) -> Self {
+        self.selinux = policy;
+        self
+    }
+
+    /// Restrict the scan to paths selected by `matcher`. Subtrees that cannot
+    /// contain any included path are pruned without being visited.
+    pub fn matcher(mut self, matcher: Matcher) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /// Persist and reuse a scan cache at `path`. On the next scan over an
+    /// unchanged rootfs, content digests are reused instead of being
+    /// recomputed (see [`crate::scancache`]).
+    pub fn cache(mut self, path: Option<&Utf8Path>) -> Self {
+        self.cache = path.map(|p| p.to_owned());
+        self
+    }
+
+    /// If set, sockets (which have no archive representation) are silently
+    /// skipped instead of producing an error. Block/char devices and FIFOs are
+    /// always recorded.
+    pub fn skip_special_files(mut self, skip: bool) -> Self {
+        self.skip_special_files = skip;
+        self
+    }
+
+    /// Exclude `paths` (and everything beneath them) from the scan; a path
+    /// ending in `/` keeps the directory itself. Paths must be absolute.
+    pub fn prune(mut self, paths: &[Utf8PathBuf]) -> Result<Self> {
+        for path in paths {
+            anyhow::ensure!(path.is_absolute(), "prune path must be absolute: {path}");
+        }
+        self.prune = paths.to_vec();
+        Ok(self)
+    }
+
+    /// Walk the rootfs and return the assembled [`FileMap`].
+    pub fn scan(&self) -> Result<FileMap> {
+        // Use a dedicated, bounded pool rather than the global one so we don't
+        // inherit an unbounded thread count on many-core hosts.
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_SCAN_THREADS);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .context("building scan thread pool")?;
+
+        // The root directory itself is not included in the map; container
+        // runtimes ignore it and we may not even have read its real perms.
+        let mut files = pool.install(|| self.scan_dir(Utf8Path::new("/")))?;
+
+        coalesce_hardlinks(&mut files);
+
+        if let Some(cache_path) = &self.cache {
+            self.apply_cache(cache_path, &mut files)
+                .with_context(|| format!("using scan cache {cache_path}"))?;
+        }
+
+        Ok(files)
+    }
+
+    /// Reuse digests from the on-disk cache for unchanged files, then rewrite
+    /// the cache with the current observations.
+    fn apply_cache(&self, cache_path: &Utf8Path, files: &mut FileMap) -> Result<()> {
+        let cache = ScanCache::load(cache_path)?;
+
+        let mut reused = 0usize;
+        let mut hashed = 0usize;
+        for (path, fi) in files.iter_mut() {
+            if fi.file_type != FileType::File || fi.digest.is_some() {
+                continue;
+            }
+            // Reuse the cached digest when the metadata still matches; otherwise
+            // hash the file now so the digest is recorded for the next run (and
+            // the cache is no longer perpetually empty).
+            if let Some(digest) = cache.lookup(path, fi.size, fi.ino, fi.mtime_secs, fi.mtime_nanos)
+            {
+                fi.digest = Some(digest.to_vec());
+                reused += 1;
             } else {
-                rel_path.as_str()
-            };
-
-            let metadata = rootfs
-                .symlink_metadata(fs_path)
-                .with_context(|| format!("getting metadata for {}", path))?;
-
-            // Check file type early, before reading xattrs
-            let file_type = match FileType::from_cap_std(&metadata.file_type()) {
-                Some(ft) => ft,
-                None => {
-                    if skip_special_files {
-                        return Ok(ControlFlow::Continue(()));
-                    } else {
-                        anyhow::bail!("special file type not supported: {}", path);
-                    }
-                }
-            };
+                let fs_path = to_fs_path(path);
+                fi.digest = Some(
+                    hash_file(self.rootfs, fs_path)
+                        .with_context(|| format!("hashing {path}"))?,
+                );
+                hashed += 1;
+            }
+        }
+        tracing::debug!(reused, hashed, "resolved content digests");
+
+        // Record everything we now know (reused or freshly hashed) and persist
+        // the cache stamped with the current wall-clock second.
+        let now = get_current_epoch()?;
+        let mut updated = ScanCache::empty();
+        for (path, fi) in files.iter() {
+            if fi.file_type != FileType::File {
+                continue;
+            }
+            if let Some(digest) = &fi.digest {
+                updated.insert(
+                    path.clone(),
+                    CacheEntry {
+                        size: fi.size,
+                        ino: fi.ino,
+                        mtime_secs: fi.mtime_secs,
+                        mtime_nanos: fi.mtime_nanos,
+                        digest: digest.clone(),
+                    },
+                );
+            }
+        }
+        updated.save(cache_path, now)?;
+
+        Ok(())
+    }
 
-            let xattrs = read_xattrs(rootfs, fs_path)
-                .with_context(|| format!("reading xattrs for {}", path))?;
+    /// Recursively scan the directory at absolute path `dir`, returning a
+    /// partial [`FileMap`] for its subtree. Children are visited in parallel.
+    fn scan_dir(&self, dir: &Utf8Path) -> Result<FileMap> {
+        // Decide which children are worth descending into before touching the
+        // filesystem, so narrow matchers don't walk the whole tree.
+        let visit = self.matcher.visit_children(dir);
+        if visit == VisitChildrenSet::Empty {
+            return Ok(FileMap::new());
+        }
+
+        let mut names = self.read_dir_names(dir)?;
+        if let VisitChildrenSet::Set(allowed) = &visit {
+            names.retain(|name| allowed.contains(name));
+        }
+
+        let partials: Vec<FileMap> = names
+            .par_iter()
+            .map(|name| self.visit(&dir.join(name)))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Reduce the per-child partial maps into one. FileMap is a BTreeMap so
+        // the merge is order-independent and deterministic.
+        let mut files = FileMap::new();
+        for partial in partials {
+            files.extend(partial);
+        }
+        Ok(files)
+    }
+
+    /// Visit a single entry at absolute path `path`, recursing into
+    /// directories. Returns the partial map rooted at `path`.
+    fn visit(&self, path: &Utf8Path) -> Result<FileMap> {
+        if self.is_pruned(path) {
+            return Ok(FileMap::new());
+        }
 
-            let file_info = FileInfo::from_metadata(&metadata, file_type, xattrs);
+        let fs_path = to_fs_path(path);
+
+        let metadata = self
+            .rootfs
+            .symlink_metadata(fs_path)
+            .with_context(|| format!("getting metadata for {path}"))?;
+
+        // Check file type early, before reading xattrs. Directories, regular
+        // files and symlinks are handled by `from_cap_std`; block/char devices
+        // and FIFOs are real entries too (the tar writer emits them as typeflag
+        // '3'/'4'/'6' with the node's `rdev`), so classify them here rather than
+        // dropping them. Only sockets, which have no archive representation,
+        // remain governed by `skip_special_files`.
+        let file_type = match FileType::from_cap_std(&metadata.file_type())
+            .or_else(|| classify_special(&metadata.file_type()))
+        {
+            Some(ft) => ft,
+            None => {
+                if self.skip_special_files {
+                    return Ok(FileMap::new());
+                } else {
+                    anyhow::bail!("special file type not supported: {path}");
+                }
+            }
+        };
+
+        let mut files = FileMap::new();
+
+        // A directory is always descended into (the recursion itself is pruned
+        // by `visit_children`), but the directory entry is only recorded if it
+        // is selected by the matcher. Non-directories that don't match are
+        // dropped, which also keeps them out of the inode tables so hardlink
+        // siblings outside the matched set aren't pulled back in.
+        let selected = self.matcher.matches(path);
+
+        if selected {
+            let mut xattrs = read_xattrs(self.rootfs, fs_path)
+                .with_context(|| format!("reading xattrs for {path}"))?;
+
+            // Synthesize a SELinux label from the policy when one is configured.
+            // read_xattrs drops any runtime-provided security.selinux, so the
+            // computed value is authoritative.
+            if let Some(policy) = &self.selinux
+                && let Some(value) = policy.xattr_value(path, file_type)
+            {
+                xattrs.push((b"security.selinux".to_vec(), value));
+            }
+
+            let mut file_info = FileInfo::from_metadata(&metadata, file_type, xattrs);
+
+            // Probe regular files for holes so large sparse files (VM images,
+            // preallocated databases) aren't fully materialized into the layer.
+            if file_type == FileType::File && !self.no_sparse {
+                file_info.sparse = probe_sparse(self.rootfs, fs_path, file_info.size)
+                    .with_context(|| format!("probing sparse map for {path}"))?;
+            }
 
             files.insert(path.to_owned(), file_info);
-            Ok::<_, anyhow::Error>(ControlFlow::Continue(()))
+        }
+
+        if file_type == FileType::Directory {
+            files.extend(self.scan_dir(path)?);
+        }
+
+        Ok(files)
+    }
+
+    /// Returns true if `path` falls under a pruned subtree. A prune path ending
+    /// in `/` excludes the directory's contents but not the directory itself.
+    fn is_pruned(&self, path: &Utf8Path) -> bool {
+        self.prune.iter().any(|p| {
+            if p.as_str().ends_with('/') {
+                path.starts_with(p) && path.as_str() != p.as_str().trim_end_matches('/')
+            } else {
+                path.starts_with(p)
+            }
         })
-        .context("failed to walk rootfs")?;
+    }
+
+    /// List the child names of the directory at absolute path `dir`.
+    fn read_dir_names(&self, dir: &Utf8Path) -> Result<Vec<String>> {
+        let fs_path = to_fs_path(dir);
+        let mut names = Vec::new();
+        for entry in self
+            .rootfs
+            .read_dir(fs_path)
+            .with_context(|| format!("reading directory {dir}"))?
+        {
+            let entry = entry.with_context(|| format!("reading entry in {dir}"))?;
+            let name = entry.file_name();
+            let name = name
+                .to_str()
+                .ok_or_else(|| anyhow::anyhow!("path is not valid UTF-8 in {dir}"))?;
+            names.push(name.to_owned());
+        }
+        Ok(names)
+    }
+}
 
-    Ok(files)
+/// Classify the non-regular node types that [`FileType::from_cap_std`] leaves
+/// unhandled but which still belong in the archive: block and character
+/// devices and FIFOs. Sockets (and anything genuinely unknown) return `None`,
+/// since they have no tar representation.
+fn classify_special(file_type: &cap_std::fs::FileType) -> Option<FileType> {
+    use cap_std::fs::FileTypeExt;
+
+    if file_type.is_block_device() {
+        Some(FileType::BlockDevice)
+    } else if file_type.is_char_device() {
+        Some(FileType::CharDevice)
+    } else if file_type.is_fifo() {
+        Some(FileType::Fifo)
+    } else {
+        None
+    }
+}
+
+/// Probe a regular file for its data segments using `SEEK_DATA`/`SEEK_HOLE`.
+///
+/// Returns `Some(segments)` of `(offset, length)` data regions when the file
+/// actually has holes, and `None` when it is dense — including the common case
+/// of a filesystem reporting a single segment that spans the whole file, which
+/// should be written as an ordinary (non-sparse) entry. The logical size is the
+/// file's size; the archived size is the sum of the segment lengths.
+fn probe_sparse(rootfs: &Dir, fs_path: &str, size: u64) -> Result<Option<Vec<(u64, u64)>>> {
+    use std::os::fd::AsRawFd;
+
+    if size == 0 {
+        return Ok(None);
+    }
+
+    let file = rootfs
+        .open(fs_path)
+        .with_context(|| format!("opening {fs_path} for sparse probe"))?
+        .into_std();
+    let fd = file.as_raw_fd();
+
+    let mut segments = Vec::new();
+    let mut offset: libc::off_t = 0;
+    let size = size as libc::off_t;
+    while offset < size {
+        // SAFETY: fd is a valid open file descriptor for the duration of the call.
+        let data = unsafe { libc::lseek(fd, offset, libc::SEEK_DATA) };
+        if data < 0 {
+            let err = std::io::Error::last_os_error();
+            // ENXIO means there is no more data past `offset`: the tail is a hole.
+            if err.raw_os_error() == Some(libc::ENXIO) {
+                break;
+            }
+            // Filesystems without hole support report EINVAL; treat as dense.
+            if err.raw_os_error() == Some(libc::EINVAL) {
+                return Ok(None);
+            }
+            return Err(err).with_context(|| format!("SEEK_DATA on {fs_path}"));
+        }
+        // SAFETY: as above.
+        let hole = unsafe { libc::lseek(fd, data, libc::SEEK_HOLE) };
+        if hole < 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("SEEK_HOLE on {fs_path}"));
+        }
+        segments.push((data as u64, (hole - data) as u64));
+        offset = hole;
+    }
+
+    // A single full-size segment is not sparse; fall back to a normal entry.
+    if segments.is_empty() || segments == [(0u64, size as u64)] {
+        return Ok(None);
+    }
+
+    // The GNU 1.0 sparse map must list data regions in order and within the
+    // logical size; a filesystem returning an out-of-order or oversized region
+    // would otherwise yield a corrupt archive entry. Treat any such result as
+    // non-sparse rather than emitting a bad map.
+    let size = size as u64;
+    let mut prev_end = 0u64;
+    for &(off, len) in &segments {
+        let Some(end) = off.checked_add(len) else {
+            return Ok(None);
+        };
+        if off < prev_end || end > size {
+            tracing::debug!(fs_path, "discarding malformed sparse map; emitting dense");
+            return Ok(None);
+        }
+        prev_end = end;
+    }
+
+    Ok(Some(segments))
+}
+
+/// Coalesce hardlinked files so their content is serialized only once.
+///
+/// For each inode with more than one link, the first path encountered is the
+/// canonical content-bearing entry; every subsequent path sharing the same
+/// `(dev, ino)` is turned into a hardlink reference to it. Because [`FileMap`]
+/// is a `BTreeMap`, iteration is in sorted path order, so the canonical target
+/// is always emitted before the links that reference it — the invariant the
+/// `tar` module needs to write a typeflag `'1'` entry. Links are kept even when
+/// the canonical entry ends up in a different chunk, since the reference is by
+/// path, not by layer.
+fn coalesce_hardlinks(files: &mut FileMap) {
+    let mut canonical: HashMap<(u64, u64), Utf8PathBuf> = HashMap::new();
+    let mut links: Vec<(Utf8PathBuf, Utf8PathBuf)> = Vec::new();
+
+    for (path, fi) in files.iter() {
+        if fi.file_type != FileType::File || fi.nlink <= 1 {
+            continue;
+        }
+        let key = (fi.dev, fi.ino);
+        match canonical.get(&key) {
+            None => {
+                canonical.insert(key, path.clone());
+            }
+            Some(target) => links.push((path.clone(), target.clone())),
+        }
+    }
+
+    for (path, target) in links {
+        if let Some(fi) = files.get_mut(&path) {
+            tracing::trace!(link = %path, target = %target, "coalesced hardlink");
+            fi.hardlink = Some(target);
+        }
+    }
+}
+
+/// Map an absolute rootfs path to the relative path `cap-std` expects, with
+/// the root mapping to ".".
+fn to_fs_path(path: &Utf8Path) -> &str {
+    let rel = path.strip_prefix("/").unwrap_or(path);
+    if rel.as_str().is_empty() {
+        "."
+    } else {
+        rel.as_str()
+    }
+}
+
+/// Hash the regular file at `fs_path` (rootfs-relative) and return its raw
+/// SHA-256 digest, used as the content identity stored in the scan cache.
+fn hash_file(rootfs: &Dir, fs_path: &str) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut file = rootfs
+        .open(fs_path)
+        .with_context(|| format!("opening {fs_path}"))?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).with_context(|| format!("reading {fs_path}"))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// How to encode an xattr key when writing a `SCHILY.xattr.<key>` PAX record.
+///
+/// libarchive and GNU tar diverge here: libarchive URL-encodes the key, GNU tar
+/// writes the bytes verbatim. We keep keys as raw bytes internally (some valid
+/// filesystem keys are not UTF-8) and apply the chosen encoding at serialization
+/// time so chunkah's output interoperates with either toolchain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum XattrEncoding {
+    /// GNU tar semantics: key bytes are passed through unchanged.
+    #[default]
+    #[value(name = "schily-raw")]
+    SchilyRaw,
+    /// libarchive semantics: non-printable/reserved bytes are percent-encoded.
+    #[value(name = "libarchive-urlencoded")]
+    LibarchiveUrlencoded,
+}
+
+impl XattrEncoding {
+    /// Encode `key` into the bytes to use after the `SCHILY.xattr.` prefix.
+    pub fn encode_key(&self, key: &[u8]) -> Vec<u8> {
+        match self {
+            XattrEncoding::SchilyRaw => key.to_vec(),
+            XattrEncoding::LibarchiveUrlencoded => {
+                let mut out = Vec::with_capacity(key.len());
+                for &b in key {
+                    // Printable ASCII is kept verbatim, except '%' (the escape
+                    // char) and '=' (the PAX key/value separator).
+                    if b.is_ascii_graphic() && b != b'%' && b != b'=' {
+                        out.push(b);
+                    } else {
+                        out.extend_from_slice(format!("%{b:02X}").as_bytes());
+                    }
+                }
+                out
+            }
+        }
+    }
 }
 
 /// Read all xattrs for a path.
-pub fn read_xattrs(rootfs: &Dir, fs_path: &str) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+///
+/// Keys are returned as raw bytes so non-UTF-8 keys (valid on the filesystem but
+/// not representable as a `String`) are preserved for the PAX serializer.
+pub fn read_xattrs(rootfs: &Dir, fs_path: &str) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>> {
     use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
 
     let xattr_list = rootfs
         .listxattrs(fs_path)
@@ -75,8 +528,8 @@ pub fn read_xattrs(rootfs: &Dir, fs_path: &str) -> anyhow::Result<Vec<(String, V
         // Skip selinux attributes for now. It would only bloat images since
         // _every_ file has SELinux attributes but they come from the container
         // runtime, not the tar layer, which is ignored. Bootable containers
-        // could use them, but don't currently. We can make it opt in once it's
-        // desirable.
+        // could use them, but don't currently (see --selinux-policy to compute
+        // them explicitly).
         if key == OsStr::new("security.selinux") {
             continue;
         }
@@ -85,15 +538,7 @@ pub fn read_xattrs(rootfs: &Dir, fs_path: &str) -> anyhow::Result<Vec<(String, V
             .getxattr(fs_path, key)
             .with_context(|| format!("reading xattr {} for {}", key.display(), fs_path))?
         {
-            // Technically, keeping the key as OsStr would be more correct,
-            // but we'll need UTF-8 to shove it in a PAX header anyway so might
-            // as well error now. Note libarchive and GNU tar differ here.
-            // libarchive does urlencoding, GNU tar just writes the key as is
-            // anyway. We'll cross that bridge when/if we get to it.
-            let key_str = key
-                .to_str()
-                .with_context(|| format!("non-UTF8 xattr key {} on {}", key.display(), fs_path))?;
-            xattrs.push((key_str.to_string(), value));
+            xattrs.push((key.as_bytes().to_vec(), value));
         }
     }
 
@@ -124,7 +569,7 @@ mod tests {
         rootfs.symlink("enoent", "broken").unwrap();
         rootfs.symlink("../../../etc/passwd", "escape").unwrap();
 
-        let files = scan_rootfs(&rootfs, false).unwrap();
+        let files = Scanner::new(&rootfs).scan().unwrap();
 
         assert_eq!(get_file_type(&files, "/realdir"), Some(FileType::Directory));
         assert_eq!(
@@ -142,7 +587,7 @@ mod tests {
         let tmp = tempfile::tempdir().unwrap();
         let rootfs = Dir::open_ambient_dir(tmp.path(), ambient_authority()).unwrap();
 
-        let files = scan_rootfs(&rootfs, false).unwrap();
+        let files = Scanner::new(&rootfs).scan().unwrap();
 
         // Should be empty. Note even the root directory is not included.
         // Root entries are not commonly in the tar stream. Container
@@ -161,7 +606,7 @@ mod tests {
         rootfs.create_dir_all("a/b/c").unwrap();
         rootfs.write("a/b/c/file", "content").unwrap();
 
-        let files = scan_rootfs(&rootfs, false).unwrap();
+        let files = Scanner::new(&rootfs).scan().unwrap();
 
         assert_eq!(get_file_type(&files, "/a"), Some(FileType::Directory));
         assert_eq!(get_file_type(&files, "/a/b"), Some(FileType::Directory));
@@ -169,6 +614,29 @@ mod tests {
         assert_eq!(get_file_type(&files, "/a/b/c/file"), Some(FileType::File));
     }
 
+    #[test]
+    fn test_scan_rootfs_prune() {
+        let tmp = tempfile::tempdir().unwrap();
+        let rootfs = Dir::open_ambient_dir(tmp.path(), ambient_authority()).unwrap();
+
+        rootfs.create_dir_all("keep").unwrap();
+        rootfs.write("keep/file", "content").unwrap();
+        rootfs.create_dir_all("drop/nested").unwrap();
+        rootfs.write("drop/nested/file", "content").unwrap();
+
+        let files = Scanner::new(&rootfs)
+            .prune(&["/drop".into()])
+            .unwrap()
+            .scan()
+            .unwrap();
+
+        assert_eq!(get_file_type(&files, "/keep"), Some(FileType::Directory));
+        assert_eq!(get_file_type(&files, "/keep/file"), Some(FileType::File));
+        // The pruned subtree is absent entirely, directory included.
+        assert!(files.get(Utf8Path::new("/drop")).is_none());
+        assert!(files.get(Utf8Path::new("/drop/nested/file")).is_none());
+    }
+
     #[test]
     fn test_scan_rootfs_special_file_type() {
         let tmp = tempfile::tempdir().unwrap();
@@ -180,7 +648,7 @@ mod tests {
         let _socket = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
 
         // By default, special file types should error
-        let result = scan_rootfs(&rootfs, false);
+        let result = Scanner::new(&rootfs).scan();
         assert!(result.is_err());
         let err = result.unwrap_err();
         let err_chain = format!("{:#}", err);
@@ -191,7 +659,7 @@ mod tests {
         );
 
         // With skip_special_files=true, the socket should be skipped
-        let files = scan_rootfs(&rootfs, true).unwrap();
+        let files = Scanner::new(&rootfs).skip_special_files(true).scan().unwrap();
 
         // Regular file should be present
         assert_eq!(get_file_type(&files, "/regular.txt"), Some(FileType::File));
@@ -0,0 +1,228 @@
+//! Persistent scan cache.
+//!
+//! Re-running chunkah over a mostly-unchanged rootfs otherwise re-stats and
+//! re-hashes every file. This cache serializes, per path, the `(size, ino,
+//! mtime, content digest)` observed on a previous run so the next run can reuse
+//! the digest whenever size, inode, and mtime all match, skipping the content
+//! re-hash.
+//!
+//! ## Same-second ("ambiguous mtime") invalidation
+//!
+//! The subtlety, borrowed from dirstate-v2's timestamp handling: mtime is
+//! stored at nanosecond precision, and we also record the wall-clock second `T`
+//! at which the cache was written. A second write to a file within that same
+//! second would not necessarily bump the mtime, so it would go undetected. We
+//! therefore treat any cached entry whose mtime second is `>= T` as *ambiguous*
+//! and always re-hash it on the next run; only entries strictly older than `T`
+//! are safe to trust.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+/// On-disk format version. Bumped whenever the serialized layout changes so a
+/// stale cache is discarded rather than misinterpreted.
+const CACHE_VERSION: u32 = 1;
+
+/// A single cached observation for a path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub size: u64,
+    pub ino: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    /// Content digest computed on the run that wrote this entry.
+    pub digest: Vec<u8>,
+}
+
+/// Root of the serialized cache file.
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    /// Wall-clock second at which this cache was written. Entries whose mtime
+    /// second is `>= written_second` are ambiguous on the next run.
+    written_second: u64,
+    entries: BTreeMap<Utf8PathBuf, CacheEntry>,
+}
+
+/// A loaded scan cache ready to be queried and rewritten.
+pub struct ScanCache {
+    written_second: u64,
+    entries: BTreeMap<Utf8PathBuf, CacheEntry>,
+}
+
+impl ScanCache {
+    /// An empty cache, as if nothing had ever been written. Every lookup misses.
+    pub fn empty() -> Self {
+        Self {
+            written_second: 0,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Load the cache at `path`. Returns an empty cache if the file does not
+    /// exist or was written by an incompatible version.
+    pub fn load(path: &Utf8Path) -> Result<Self> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::empty()),
+            Err(e) => return Err(e).with_context(|| format!("reading scan cache {path}")),
+        };
+
+        let file: CacheFile = match serde_json::from_slice(&bytes) {
+            Ok(file) => file,
+            // A corrupt or truncated cache is never fatal; just rebuild.
+            Err(e) => {
+                tracing::warn!(error = %e, path = %path, "ignoring unreadable scan cache");
+                return Ok(Self::empty());
+            }
+        };
+
+        if file.version != CACHE_VERSION {
+            tracing::debug!(
+                found = file.version,
+                expected = CACHE_VERSION,
+                "ignoring scan cache with mismatched version"
+            );
+            return Ok(Self::empty());
+        }
+
+        Ok(Self {
+            written_second: file.written_second,
+            entries: file.entries,
+        })
+    }
+
+    /// Look up a trusted digest for `path`.
+    ///
+    /// Returns the cached digest only when size, inode, and mtime all match and
+    /// the entry is not ambiguous (its mtime second is strictly older than the
+    /// second at which the cache was written). Otherwise the caller must
+    /// re-hash.
+    pub fn lookup(
+        &self,
+        path: &Utf8Path,
+        size: u64,
+        ino: u64,
+        mtime_secs: u64,
+        mtime_nanos: u32,
+    ) -> Option<&[u8]> {
+        let entry = self.entries.get(path)?;
+        if entry.size != size
+            || entry.ino != ino
+            || entry.mtime_secs != mtime_secs
+            || entry.mtime_nanos != mtime_nanos
+        {
+            return None;
+        }
+        // Ambiguous: a same-second rewrite could have changed the content
+        // without bumping the mtime, so we can't trust this entry.
+        if mtime_secs >= self.written_second {
+            return None;
+        }
+        Some(&entry.digest)
+    }
+
+    /// Record an observation to be written on the next [`Self::save`].
+    pub fn insert(&mut self, path: Utf8PathBuf, entry: CacheEntry) {
+        self.entries.insert(path, entry);
+    }
+
+    /// Persist the cache to `path`, stamping it with `now_second` as the
+    /// wall-clock second of the write.
+    pub fn save(&self, path: &Utf8Path, now_second: u64) -> Result<()> {
+        let file = CacheFile {
+            version: CACHE_VERSION,
+            written_second: now_second,
+            entries: self.entries.clone(),
+        };
+        let bytes = serde_json::to_vec(&file).context("serializing scan cache")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating scan cache directory {parent}"))?;
+        }
+        std::fs::write(path, bytes).with_context(|| format!("writing scan cache {path}"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(size: u64, ino: u64, secs: u64, nanos: u32) -> CacheEntry {
+        CacheEntry {
+            size,
+            ino,
+            mtime_secs: secs,
+            mtime_nanos: nanos,
+            digest: vec![0xab; 4],
+        }
+    }
+
+    #[test]
+    fn test_hit_on_exact_match() {
+        let mut cache = ScanCache::empty();
+        // Written at second 100; entry's mtime is strictly older.
+        cache.written_second = 100;
+        cache.insert("/a".into(), entry(10, 1, 90, 500));
+
+        assert_eq!(cache.lookup(Utf8Path::new("/a"), 10, 1, 90, 500), Some(&[0xab; 4][..]));
+    }
+
+    #[test]
+    fn test_miss_on_metadata_mismatch() {
+        let mut cache = ScanCache::empty();
+        cache.written_second = 100;
+        cache.insert("/a".into(), entry(10, 1, 90, 500));
+
+        // size / ino / mtime mismatches each miss.
+        assert!(cache.lookup(Utf8Path::new("/a"), 11, 1, 90, 500).is_none());
+        assert!(cache.lookup(Utf8Path::new("/a"), 10, 2, 90, 500).is_none());
+        assert!(cache.lookup(Utf8Path::new("/a"), 10, 1, 91, 500).is_none());
+        assert!(cache.lookup(Utf8Path::new("/a"), 10, 1, 90, 501).is_none());
+        assert!(cache.lookup(Utf8Path::new("/b"), 10, 1, 90, 500).is_none());
+    }
+
+    #[test]
+    fn test_same_second_is_ambiguous() {
+        let mut cache = ScanCache::empty();
+        cache.written_second = 100;
+        // mtime second == written second: a second write in that same second
+        // would not bump mtime, so the entry must be treated as ambiguous.
+        cache.insert("/a".into(), entry(10, 1, 100, 0));
+        assert!(cache.lookup(Utf8Path::new("/a"), 10, 1, 100, 0).is_none());
+
+        // A later-second mtime (clock skew / future write) is ambiguous too.
+        cache.insert("/b".into(), entry(10, 2, 101, 0));
+        assert!(cache.lookup(Utf8Path::new("/b"), 10, 2, 101, 0).is_none());
+    }
+
+    #[test]
+    fn test_roundtrip_and_version() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::try_from(tmp.path().join("cache.json")).unwrap();
+
+        let mut cache = ScanCache::empty();
+        cache.insert("/a".into(), entry(10, 1, 90, 500));
+        cache.save(&path, 100).unwrap();
+
+        let reloaded = ScanCache::load(&path).unwrap();
+        assert_eq!(
+            reloaded.lookup(Utf8Path::new("/a"), 10, 1, 90, 500),
+            Some(&[0xab; 4][..])
+        );
+
+        // A cache written with a different version is discarded.
+        let raw = std::fs::read_to_string(&path).unwrap();
+        let bumped = raw.replace(
+            &format!("\"version\":{CACHE_VERSION}"),
+            &format!("\"version\":{}", CACHE_VERSION + 1),
+        );
+        std::fs::write(&path, bumped).unwrap();
+        let reloaded = ScanCache::load(&path).unwrap();
+        assert!(reloaded.lookup(Utf8Path::new("/a"), 10, 1, 90, 500).is_none());
+    }
+}
@@ -0,0 +1,204 @@
+//! Compute SELinux file labels from a policy `file_contexts` database.
+//!
+//! `read_xattrs` drops `security.selinux` by default because, for ordinary
+//! container images, those labels come from the container runtime rather than
+//! the tar layer. For bootable (bootc/ostree) images the tar stream *is* read
+//! back, so chunkah needs to be able to synthesize correct labels itself.
+//!
+//! This module loads a `file_contexts` file — lines of
+//! `regex [type-spec] user:role:type:level` — and, following libselinux
+//! matching semantics, resolves the context for a given path and file type:
+//! rules are filtered by the entry's file type, and among the matching regexes
+//! the most-specific one wins (longer literal stem first, then a non-wildcard
+//! regex over a wildcard one, then the last-listed rule).
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use regex::Regex;
+
+use crate::components::FileType;
+
+/// A parsed `file_contexts` database.
+pub struct FileContexts {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    regex: Regex,
+    /// Length of the leading literal (glob-free) portion of the pattern.
+    stem_len: usize,
+    /// Whether the pattern contains any regex metacharacters after the stem.
+    has_meta: bool,
+    /// Optional file-type restriction from the `-d`/`--`/`-l`/... spec.
+    file_type: Option<FileType>,
+    /// The context string, or `None` for an explicit `<<none>>`.
+    context: Option<String>,
+}
+
+impl FileContexts {
+    /// Load and compile a `file_contexts` policy file.
+    pub fn load(path: &Utf8Path) -> Result<Self> {
+        let content =
+            std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+        Self::parse(&content).with_context(|| format!("parsing {path}"))
+    }
+
+    /// Parse the contents of a `file_contexts` file.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let pattern = tokens
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("empty rule on line {}", lineno + 1))?;
+
+            // The optional type spec (e.g. `--`, `-d`, `-l`) starts with '-'.
+            let (file_type, context) = match tokens.next() {
+                Some(tok) if tok.starts_with('-') => {
+                    let ft = parse_type_spec(tok).with_context(|| {
+                        format!("unknown type spec {tok:?} on line {}", lineno + 1)
+                    })?;
+                    let ctx = tokens.next().ok_or_else(|| {
+                        anyhow::anyhow!("missing context on line {}", lineno + 1)
+                    })?;
+                    (ft, ctx)
+                }
+                Some(ctx) => (None, ctx),
+                None => anyhow::bail!("missing context on line {}", lineno + 1),
+            };
+
+            let (stem_len, has_meta) = stem_info(pattern);
+            // Anchor the pattern so it matches the whole path, as libselinux does.
+            let regex = Regex::new(&format!("^(?:{pattern})$"))
+                .with_context(|| format!("invalid regex {pattern:?} on line {}", lineno + 1))?;
+
+            rules.push(Rule {
+                regex,
+                stem_len,
+                has_meta,
+                file_type,
+                context: (context != "<<none>>").then(|| context.to_string()),
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    /// Resolve the SELinux context for `path` of the given `file_type`.
+    ///
+    /// Returns the context string (without a trailing NUL) or `None` when no
+    /// rule matches or the matching rule is `<<none>>`.
+    pub fn lookup(&self, path: &Utf8Path, file_type: FileType) -> Option<&str> {
+        let path = path.as_str();
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.file_type.is_none_or(|ft| ft == file_type))
+            .filter(|(_, r)| r.regex.is_match(path))
+            // Most-specific wins: longest literal stem, then a fully-literal
+            // pattern over a wildcard one, then the last-listed rule.
+            .max_by_key(|(idx, r)| (r.stem_len, !r.has_meta, *idx))
+            .and_then(|(_, r)| r.context.as_deref())
+    }
+
+    /// Resolve the context and return it as a NUL-terminated xattr value, ready
+    /// to be attached as `security.selinux`.
+    pub fn xattr_value(&self, path: &Utf8Path, file_type: FileType) -> Option<Vec<u8>> {
+        self.lookup(path, file_type).map(|ctx| {
+            let mut value = ctx.as_bytes().to_vec();
+            value.push(0);
+            value
+        })
+    }
+}
+
+/// Map a `file_contexts` type spec to the file type it restricts to.
+fn parse_type_spec(spec: &str) -> Result<Option<FileType>> {
+    Ok(match spec {
+        "--" => Some(FileType::File),
+        "-d" => Some(FileType::Directory),
+        "-l" => Some(FileType::Symlink),
+        "-b" => Some(FileType::BlockDevice),
+        "-c" => Some(FileType::CharDevice),
+        "-p" => Some(FileType::Fifo),
+        "-s" => Some(FileType::Socket),
+        other => anyhow::bail!("unsupported type spec: {other}"),
+    })
+}
+
+/// Compute the length of the leading literal portion of `pattern` and whether
+/// it contains any regex metacharacters beyond that stem.
+fn stem_info(pattern: &str) -> (usize, bool) {
+    const META: &[char] = &['.', '^', '$', '?', '*', '+', '[', ']', '{', '}', '(', ')', '|', '\\'];
+    let stem_len = pattern.chars().take_while(|c| !META.contains(c)).count();
+    let has_meta = stem_len != pattern.chars().count();
+    (stem_len, has_meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"
+# a comment
+/.*                     system_u:object_r:default_t:s0
+/usr/bin/.*     --      system_u:object_r:bin_t:s0
+/usr/bin/sh     -l      system_u:object_r:sh_link_t:s0
+/etc/shadow     --      system_u:object_r:shadow_t:s0
+/var/tmp/junk   --      <<none>>
+"#;
+
+    #[test]
+    fn test_most_specific_wins() {
+        let fc = FileContexts::parse(POLICY).unwrap();
+
+        // /usr/bin/bash matches both /.* and /usr/bin/.*; the longer stem wins.
+        assert_eq!(
+            fc.lookup(Utf8Path::new("/usr/bin/bash"), FileType::File),
+            Some("system_u:object_r:bin_t:s0")
+        );
+        // Exact literal over the shared prefix.
+        assert_eq!(
+            fc.lookup(Utf8Path::new("/etc/shadow"), FileType::File),
+            Some("system_u:object_r:shadow_t:s0")
+        );
+        // Falls back to the catch-all.
+        assert_eq!(
+            fc.lookup(Utf8Path::new("/opt/thing"), FileType::File),
+            Some("system_u:object_r:default_t:s0")
+        );
+    }
+
+    #[test]
+    fn test_type_filtering() {
+        let fc = FileContexts::parse(POLICY).unwrap();
+        // As a symlink, /usr/bin/sh picks the `-l` rule, not the `--` one.
+        assert_eq!(
+            fc.lookup(Utf8Path::new("/usr/bin/sh"), FileType::Symlink),
+            Some("system_u:object_r:sh_link_t:s0")
+        );
+        // As a regular file, the `-l` rule does not apply.
+        assert_eq!(
+            fc.lookup(Utf8Path::new("/usr/bin/sh"), FileType::File),
+            Some("system_u:object_r:bin_t:s0")
+        );
+    }
+
+    #[test]
+    fn test_none_and_nul_termination() {
+        let fc = FileContexts::parse(POLICY).unwrap();
+        // <<none>> means no label at all.
+        assert_eq!(fc.lookup(Utf8Path::new("/var/tmp/junk"), FileType::File), None);
+        assert!(fc.xattr_value(Utf8Path::new("/var/tmp/junk"), FileType::File).is_none());
+
+        let value = fc
+            .xattr_value(Utf8Path::new("/etc/shadow"), FileType::File)
+            .unwrap();
+        assert_eq!(value.last(), Some(&0u8));
+        assert_eq!(&value[..value.len() - 1], b"system_u:object_r:shadow_t:s0");
+    }
+}
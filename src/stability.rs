@@ -0,0 +1,300 @@
+//! Empirical stability estimation from build history.
+//!
+//! `PackItem::stability` is the weakest input to the packing algorithm when it
+//! is a hand-guessed probability. This module learns it instead: given a
+//! time-ordered stream of past build manifests (per-component content digests),
+//! it treats each successive build pair as a change observation — `1` if a
+//! component's digest was unchanged, `0` otherwise — and maintains an
+//! exponentially-weighted no-change rate per component:
+//!
+//! ```text
+//! stability = alpha * observed + (1 - alpha) * stability
+//! ```
+//!
+//! Components with little history fall back to a neutral prior (0.5 by default)
+//! until they clear a minimum-observation floor, so a single coincidental
+//! no-change doesn't get trusted as stability. The state (observation count and
+//! current estimate per component key) is persisted across runs so the packer
+//! keeps learning which components actually churn.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+
+use crate::packing::PackItem;
+
+/// On-disk format version; a mismatch discards the state rather than
+/// misinterpreting it.
+const STATE_VERSION: u32 = 1;
+
+/// Default decay weight for new observations.
+const DEFAULT_ALPHA: f64 = 0.3;
+/// Default prior for components without enough history to trust.
+const DEFAULT_PRIOR: f64 = 0.5;
+/// Default number of observations before the learned estimate is trusted.
+const DEFAULT_MIN_OBSERVATIONS: u64 = 3;
+
+/// Learned state for a single component key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ComponentState {
+    /// Current exponentially-weighted no-change rate.
+    stability: f64,
+    /// Number of change observations folded in so far.
+    observations: u64,
+    /// Digest seen in the most recently ingested build.
+    last_digest: Vec<u8>,
+}
+
+/// Root of the serialized state file.
+#[derive(Debug, Serialize, Deserialize)]
+struct StateFile {
+    version: u32,
+    alpha: f64,
+    prior: f64,
+    min_observations: u64,
+    states: BTreeMap<String, ComponentState>,
+}
+
+/// Learns per-component stability from a stream of build manifests.
+pub struct StabilityEstimator {
+    alpha: f64,
+    prior: f64,
+    min_observations: u64,
+    states: BTreeMap<String, ComponentState>,
+}
+
+impl Default for StabilityEstimator {
+    fn default() -> Self {
+        Self {
+            alpha: DEFAULT_ALPHA,
+            prior: DEFAULT_PRIOR,
+            min_observations: DEFAULT_MIN_OBSERVATIONS,
+            states: BTreeMap::new(),
+        }
+    }
+}
+
+impl StabilityEstimator {
+    /// A fresh estimator with default tuning and no history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the decay weight applied to each new observation (0.0 to 1.0).
+    pub fn alpha(mut self, alpha: f64) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Set the prior returned for components below the observation floor.
+    pub fn prior(mut self, prior: f64) -> Self {
+        self.prior = prior;
+        self
+    }
+
+    /// Set how many observations a component needs before its learned estimate
+    /// is trusted over the prior.
+    pub fn min_observations(mut self, min_observations: u64) -> Self {
+        self.min_observations = min_observations;
+        self
+    }
+
+    /// Fold a single build manifest (component key -> content digest) into the
+    /// estimate. The first time a component is seen only records its digest; on
+    /// every later build the digest is compared against the previous one and the
+    /// resulting no-change observation updates the EWMA.
+    pub fn ingest_build(&mut self, manifest: &BTreeMap<String, Vec<u8>>) {
+        for (key, digest) in manifest {
+            match self.states.get_mut(key) {
+                Some(state) => {
+                    let observed = if state.last_digest == *digest { 1.0 } else { 0.0 };
+                    state.stability = self.alpha * observed + (1.0 - self.alpha) * state.stability;
+                    state.observations += 1;
+                    state.last_digest = digest.clone();
+                }
+                None => {
+                    self.states.insert(
+                        key.clone(),
+                        ComponentState {
+                            stability: self.prior,
+                            observations: 0,
+                            last_digest: digest.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Fold a time-ordered sequence of build manifests in order.
+    pub fn ingest_history(&mut self, builds: &[BTreeMap<String, Vec<u8>>]) {
+        for manifest in builds {
+            self.ingest_build(manifest);
+        }
+    }
+
+    /// The stability estimate for a component key: the learned EWMA once enough
+    /// observations have accumulated, otherwise the neutral prior.
+    pub fn stability(&self, key: &str) -> f64 {
+        match self.states.get(key) {
+            Some(state) if state.observations >= self.min_observations => state.stability,
+            _ => self.prior,
+        }
+    }
+
+    /// Build the `PackItem`s for `calculate_packing` by pairing each component's
+    /// size with its learned stability. Iteration order follows `sizes` (a
+    /// `BTreeMap`, so sorted by key) for a deterministic result the caller can
+    /// map back to component keys.
+    pub fn to_pack_items(&self, sizes: &BTreeMap<String, u64>) -> Vec<PackItem> {
+        sizes
+            .iter()
+            .map(|(key, &size)| PackItem {
+                size,
+                stability: self.stability(key),
+                previous_group: None,
+            })
+            .collect()
+    }
+
+    /// Load persisted state from `path`. Returns a fresh estimator if the file
+    /// does not exist or was written by an incompatible version.
+    pub fn load(path: &Utf8Path) -> Result<Self> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e).with_context(|| format!("reading stability state {path}")),
+        };
+
+        let file: StateFile = match serde_json::from_slice(&bytes) {
+            Ok(file) => file,
+            Err(e) => {
+                tracing::warn!(error = %e, path = %path, "ignoring unreadable stability state");
+                return Ok(Self::new());
+            }
+        };
+
+        if file.version != STATE_VERSION {
+            tracing::debug!(
+                found = file.version,
+                expected = STATE_VERSION,
+                "ignoring stability state with mismatched version"
+            );
+            return Ok(Self::new());
+        }
+
+        Ok(Self {
+            alpha: file.alpha,
+            prior: file.prior,
+            min_observations: file.min_observations,
+            states: file.states,
+        })
+    }
+
+    /// Persist the current state to `path`.
+    pub fn save(&self, path: &Utf8Path) -> Result<()> {
+        let file = StateFile {
+            version: STATE_VERSION,
+            alpha: self.alpha,
+            prior: self.prior,
+            min_observations: self.min_observations,
+            states: self.states.clone(),
+        };
+        let bytes = serde_json::to_vec(&file).context("serializing stability state")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating stability state directory {parent}"))?;
+        }
+        std::fs::write(path, bytes).with_context(|| format!("writing stability state {path}"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(pairs: &[(&str, &[u8])]) -> BTreeMap<String, Vec<u8>> {
+        pairs
+            .iter()
+            .map(|(k, d)| (k.to_string(), d.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_unchanged_component_trends_stable() {
+        let mut est = StabilityEstimator::new().min_observations(1);
+        // Same digest across five builds: stability climbs toward 1.0.
+        for _ in 0..5 {
+            est.ingest_build(&build(&[("pkg", b"aaaa")]));
+        }
+        let s = est.stability("pkg");
+        assert!(s > est.prior, "stability {s} should exceed the prior");
+    }
+
+    #[test]
+    fn test_churning_component_trends_unstable() {
+        let mut est = StabilityEstimator::new().min_observations(1);
+        for i in 0..5u8 {
+            est.ingest_build(&build(&[("pkg", &[i])]));
+        }
+        let s = est.stability("pkg");
+        assert!(s < est.prior, "stability {s} should fall below the prior");
+    }
+
+    #[test]
+    fn test_prior_until_min_observations() {
+        let mut est = StabilityEstimator::new().min_observations(3);
+        // One build seeds, a second gives a single observation: still below the
+        // floor, so the prior is returned rather than the volatile estimate.
+        est.ingest_build(&build(&[("pkg", b"aaaa")]));
+        est.ingest_build(&build(&[("pkg", b"bbbb")]));
+        assert_eq!(est.stability("pkg"), DEFAULT_PRIOR);
+        // An unknown component is always the prior.
+        assert_eq!(est.stability("missing"), DEFAULT_PRIOR);
+    }
+
+    #[test]
+    fn test_to_pack_items() {
+        let mut est = StabilityEstimator::new().min_observations(1);
+        est.ingest_build(&build(&[("a", b"x"), ("b", b"y")]));
+        est.ingest_build(&build(&[("a", b"x"), ("b", b"z")]));
+
+        let sizes: BTreeMap<String, u64> =
+            [("a".to_string(), 100), ("b".to_string(), 200)].into_iter().collect();
+        let items = est.to_pack_items(&sizes);
+        assert_eq!(items.len(), 2);
+        // sorted by key: a first, then b
+        assert_eq!(items[0].size, 100);
+        assert_eq!(items[1].size, 200);
+        // "a" never changed, "b" did, so a should be more stable than b
+        assert!(items[0].stability > items[1].stability);
+    }
+
+    #[test]
+    fn test_persistence_roundtrip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = camino::Utf8PathBuf::try_from(tmp.path().join("stability.json")).unwrap();
+
+        let mut est = StabilityEstimator::new().alpha(0.4).min_observations(1);
+        est.ingest_build(&build(&[("pkg", b"aaaa")]));
+        est.ingest_build(&build(&[("pkg", b"aaaa")]));
+        let before = est.stability("pkg");
+        est.save(&path).unwrap();
+
+        let reloaded = StabilityEstimator::load(&path).unwrap();
+        assert_eq!(reloaded.stability("pkg"), before);
+
+        // A state file with a different version is discarded.
+        let raw = std::fs::read_to_string(&path).unwrap();
+        let bumped = raw.replace(
+            &format!("\"version\":{STATE_VERSION}"),
+            &format!("\"version\":{}", STATE_VERSION + 1),
+        );
+        std::fs::write(&path, bumped).unwrap();
+        let reloaded = StabilityEstimator::load(&path).unwrap();
+        assert_eq!(reloaded.stability("pkg"), DEFAULT_PRIOR);
+    }
+}